@@ -96,7 +96,7 @@ impl Worker {
                         for _i in 0..MINING_ITERATION {
                             let nonce_u256 = U256::from(nonce);
                             let hash = compute(&nonce_u256, &block_hash);
-                            if ProofOfWorkProblem::validate_hash_against_boundary(&hash, &nonce_u256, &boundary) {
+                            if ProofOfWorkProblem::validate_hash_against_boundary_with(&hash, &nonce_u256, &boundary, bg_handle.pow_config.lower_bound_bits) {
                                 // problem solved
                                 match solution_sender
                                     .send(ProofOfWorkSolution { nonce: nonce_u256 })
@@ -605,11 +605,12 @@ impl BlockGenerator {
         );
         let mut nonce: u64 = rand::random();
         loop {
-            if validate(
+            if validate_with(
                 &problem,
                 &ProofOfWorkSolution {
                     nonce: U256::from(nonce),
                 },
+                self.pow_config.lower_bound_bits,
             ) {
                 block.block_header.set_nonce(U256::from(nonce));
                 break;
@@ -664,6 +665,7 @@ impl BlockGenerator {
             listen_addr: bg.pow_config.stratum_listen_addr.clone(),
             port: bg.pow_config.stratum_port,
             secret: bg.pow_config.stratum_secret,
+            lower_bound_bits: bg.pow_config.lower_bound_bits,
         };
         let stratum = Stratum::start(&cfg, solution_sender)
             .expect("Failed to start Stratum service.");
@@ -735,9 +737,10 @@ impl BlockGenerator {
                     trace!("new solution: {:?}", new_solution);
                     // check if the block received valid
                     if new_solution.is_ok()
-                        && !validate(
+                        && !validate_with(
                             &current_problem.unwrap(),
                             &new_solution.unwrap(),
+                            bg.pow_config.lower_bound_bits,
                         )
                     {
                         warn!(
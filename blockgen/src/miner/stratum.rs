@@ -30,7 +30,7 @@ use cfx_stratum::{
     Stratum as StratumService,
 };
 use cfx_types::{H256, U256};
-use cfxcore::pow::{validate, ProofOfWorkProblem, ProofOfWorkSolution};
+use cfxcore::pow::{validate_with, ProofOfWorkProblem, ProofOfWorkSolution};
 use log::{info, trace, warn};
 use parking_lot::Mutex;
 use std::{
@@ -48,6 +48,8 @@ pub struct Options {
     pub port: u16,
     /// Secret for peers
     pub secret: Option<H256>,
+    /// PoW lower bound bits the submitted solutions are validated with
+    pub lower_bound_bits: u32,
 }
 
 fn clean_0x(s: &str) -> &str {
@@ -113,6 +115,7 @@ impl fmt::Display for PayloadError {
 pub struct StratumJobDispatcher {
     current_problem: Mutex<Option<ProofOfWorkProblem>>,
     solution_sender: Mutex<mpsc::Sender<ProofOfWorkSolution>>,
+    lower_bound_bits: u32,
 }
 
 impl JobDispatcher for StratumJobDispatcher {
@@ -143,7 +146,7 @@ impl JobDispatcher for StratumJobDispatcher {
                         .into(),
                     ));
                 }
-                if !validate(&prob, &sol) {
+                if !validate_with(&prob, &sol, self.lower_bound_bits) {
                     return Err(StratumServiceError::InvalidSolution(
                         format!(
                             "Incorrect Nonce! worker_id = {}!",
@@ -180,10 +183,12 @@ impl StratumJobDispatcher {
     /// New stratum job dispatcher given the miner and client
     fn new(
         solution_sender: mpsc::Sender<ProofOfWorkSolution>,
+        lower_bound_bits: u32,
     ) -> StratumJobDispatcher {
         StratumJobDispatcher {
             current_problem: Mutex::new(None),
             solution_sender: Mutex::new(solution_sender),
+            lower_bound_bits,
         }
     }
 
@@ -248,7 +253,10 @@ impl Stratum {
     ) -> Result<Stratum, Error> {
         use std::net::IpAddr;
 
-        let dispatcher = Arc::new(StratumJobDispatcher::new(solution_sender));
+        let dispatcher = Arc::new(StratumJobDispatcher::new(
+            solution_sender,
+            options.lower_bound_bits,
+        ));
 
         let stratum_svc = StratumService::start(
             &SocketAddr::new(
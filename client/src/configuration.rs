@@ -110,6 +110,7 @@ build_config! {
         // Snapshot Epoch Count is a consensus parameter. This flag overrides
         // the parameter, which only take effect in `dev` mode.
         (dev_snapshot_epoch_count, (u32), SNAPSHOT_EPOCHS_CAPACITY)
+        (difficulty_adjustment_factor, (Option<u64>), None)
         (era_epoch_count, (u64), ERA_DEFAULT_EPOCH_COUNT)
         (heavy_block_difficulty_ratio, (u64), HEAVY_BLOCK_DEFAULT_DIFFICULTY_RATIO)
         (genesis_accounts, (Option<String>), None)
@@ -458,6 +459,7 @@ impl Configuration {
             stratum_listen_addr,
             self.raw_conf.stratum_port,
             stratum_secret,
+            self.raw_conf.difficulty_adjustment_factor,
         )
     }
 
@@ -467,6 +469,7 @@ impl Configuration {
             self.raw_conf.referee_bound,
             self.raw_conf.max_block_size_in_bytes,
             self.raw_conf.transaction_epoch_bound,
+            self.pow_config().lower_bound_bits,
         )
     }
 
@@ -630,6 +633,7 @@ impl Configuration {
                 "sqlite" => DbType::Sqlite,
                 _ => panic!("Invalid block_db_type parameter!"),
             },
+            self.pow_config().lower_bound_bits,
         )
     }
 
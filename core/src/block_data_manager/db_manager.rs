@@ -55,10 +55,14 @@ fn sqlite_db_table(table: DBTable) -> String {
 
 pub struct DBManager {
     table_db: HashMap<DBTable, Box<dyn KeyValueDbTrait<ValueType = Box<[u8]>>>>,
+    // Used to recompute the PoW quality of the headers loaded from the db.
+    pow_lower_bound_bits: u32,
 }
 
 impl DBManager {
-    pub fn new_from_rocksdb(db: Arc<SystemDB>) -> Self {
+    pub fn new_from_rocksdb(
+        db: Arc<SystemDB>, pow_lower_bound_bits: u32,
+    ) -> Self {
         let mut table_db = HashMap::new();
         for table in vec![
             DBTable::Misc,
@@ -75,12 +79,15 @@ impl DBManager {
                     as Box<dyn KeyValueDbTrait<ValueType = Box<[u8]>>>,
             );
         }
-        Self { table_db }
+        Self {
+            table_db,
+            pow_lower_bound_bits,
+        }
     }
 }
 
 impl DBManager {
-    pub fn new_from_sqlite(db_path: &Path) -> Self {
+    pub fn new_from_sqlite(db_path: &Path, pow_lower_bound_bits: u32) -> Self {
         if let Err(e) = fs::create_dir_all(db_path) {
             panic!("Error creating database directory: {:?}", e);
         }
@@ -113,7 +120,10 @@ impl DBManager {
                     as Box<dyn KeyValueDbTrait<ValueType = Box<[u8]>>>,
             );
         }
-        Self { table_db }
+        Self {
+            table_db,
+            pow_lower_bound_bits,
+        }
     }
 }
 
@@ -137,8 +147,9 @@ impl DBManager {
     pub fn block_header_from_db(&self, hash: &H256) -> Option<BlockHeader> {
         let mut block_header =
             self.load_decodable_val(DBTable::Blocks, hash.as_bytes())?;
-        VerificationConfig::compute_pow_hash_and_fill_header_pow_quality(
+        VerificationConfig::compute_pow_hash_and_fill_header_pow_quality_with(
             &mut block_header,
+            self.pow_lower_bound_bits,
         );
         Some(block_header)
     }
@@ -6,6 +6,7 @@ use crate::{
     cache_config::CacheConfig,
     cache_manager::{CacheId, CacheManager, CacheSize},
     ext_db::SystemDB,
+    parameters::pow::TARGET_DIFFICULTY_CACHE_DEFAULT_CAPACITY,
     pow::TargetDifficultyManager,
     storage::{
         state_manager::StateIndex, utils::guarded_value::*,
@@ -317,10 +318,13 @@ impl BlockDataManager {
             worker_pool,
         );
         let db_manager = match config.db_type {
-            DbType::Rocksdb => DBManager::new_from_rocksdb(db),
-            DbType::Sqlite => {
-                DBManager::new_from_sqlite(Path::new("./sqlite_db"))
+            DbType::Rocksdb => {
+                DBManager::new_from_rocksdb(db, config.pow_lower_bound_bits)
             }
+            DbType::Sqlite => DBManager::new_from_sqlite(
+                Path::new("./sqlite_db"),
+                config.pow_lower_bound_bits,
+            ),
         };
 
         let data_man = Self {
@@ -341,7 +345,9 @@ impl BlockDataManager {
             cache_man,
             instance_id: Mutex::new(0),
             config,
-            target_difficulty_manager: TargetDifficultyManager::new(),
+            target_difficulty_manager: TargetDifficultyManager::new(
+                TARGET_DIFFICULTY_CACHE_DEFAULT_CAPACITY,
+            ),
             cur_consensus_era_genesis_hash: RwLock::new(true_genesis.hash()),
             cur_consensus_era_stable_hash: RwLock::new(true_genesis.hash()),
             tx_data_manager,
@@ -1364,6 +1370,8 @@ pub struct DataManagerConfiguration {
     persist_tx_index: bool,
     tx_cache_index_maintain_timeout: Duration,
     db_type: DbType,
+    // Must match `ProofOfWorkConfig::lower_bound_bits`.
+    pow_lower_bound_bits: u32,
 }
 
 impl MallocSizeOf for DataManagerConfiguration {
@@ -1373,13 +1381,14 @@ impl MallocSizeOf for DataManagerConfiguration {
 impl DataManagerConfiguration {
     pub fn new(
         persist_tx_index: bool, tx_cache_index_maintain_timeout: Duration,
-        db_type: DbType,
+        db_type: DbType, pow_lower_bound_bits: u32,
     ) -> Self
     {
         Self {
             persist_tx_index,
             tx_cache_index_maintain_timeout,
             db_type,
+            pow_lower_bound_bits,
         }
     }
 }
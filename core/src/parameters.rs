@@ -153,6 +153,20 @@ pub mod pow {
     // and the confirmation speed
     pub const TARGET_AVERAGE_BLOCK_GENERATION_PERIOD: u64 = 500000;
     pub const INITIAL_DIFFICULTY: u64 = 5_000_000;
+    // The number of nonce bits right below the highest bit which make up the
+    // lower bound of the PoW, see `pow::nonce_to_lower_bound`.
+    pub const POW_LOWER_BOUND_BITS: u32 = 127;
+    // Each difficulty adjustment moves the difficulty by this fraction of the
+    // way towards the target, before the adjustment bound is applied. 1/1
+    // moves all the way.
+    pub const DIFFICULTY_ADJUSTMENT_DAMPING_NUMERATOR: u64 = 1;
+    pub const DIFFICULTY_ADJUSTMENT_DAMPING_DENOMINATOR: u64 = 1;
+    // The difference from the recomputed difficulty below which
+    // `pow::audit_difficulty_adjustments` treats a recorded difficulty as
+    // matching, to allow for rounding.
+    pub const DIFFICULTY_AUDIT_TOLERANCE: u64 = 1;
+    // The number of adjustment periods whose target difficulty is cached.
+    pub const TARGET_DIFFICULTY_CACHE_DEFAULT_CAPACITY: usize = 4096;
 }
 
 pub mod block {
@@ -221,6 +235,17 @@ pub mod staking {
     }
 }
 
+//////////////////////////////////////////////////////////////////////
+/* Signal and Slots begin */
+pub mod signal {
+    // Whether a slot may be created with a gas ratio below 1, which lets its
+    // slot transactions pay less than the base gas price. See
+    // `SlotInfo::validate_min_ratio`.
+    pub const ALLOW_SUB_BASE_SLOT_GAS_RATIO: bool = true;
+}
+/* Signal and Slots end */
+//////////////////////////////////////////////////////////////////////
+
 pub mod light {
     use std::time::Duration;
 
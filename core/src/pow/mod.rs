@@ -6,10 +6,11 @@ use crate::{
     block_data_manager::BlockDataManager, hash::keccak, parameters::pow::*,
 };
 use cfx_types::{BigEndianHash, H256, U256, U512};
+use lru::LruCache;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
-use parking_lot::RwLock;
-use std::{collections::HashMap, convert::TryFrom};
+use parking_lot::Mutex;
+use std::{convert::TryFrom, error, fmt, mem, net::IpAddr};
 
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
 pub struct ProofOfWorkProblem {
@@ -34,12 +35,46 @@ impl ProofOfWorkProblem {
     pub fn validate_hash_against_boundary(
         hash: &H256, nonce: &U256, boundary: &U256,
     ) -> bool {
-        let lower_bound = nonce_to_lower_bound(nonce);
+        Self::validate_hash_against_boundary_with(
+            hash,
+            nonce,
+            boundary,
+            POW_LOWER_BOUND_BITS,
+        )
+    }
+
+    /// Same as `validate_hash_against_boundary`, with the lower bound
+    /// derived from `lower_bound_bits` bits of the nonce.
+    #[inline]
+    pub fn validate_hash_against_boundary_with(
+        hash: &H256, nonce: &U256, boundary: &U256, lower_bound_bits: u32,
+    ) -> bool {
+        let lower_bound = nonce_to_lower_bound_with(nonce, lower_bound_bits);
         let (against_lower_bound_u256, _) =
             BigEndianHash::into_uint(hash).overflowing_sub(lower_bound);
         against_lower_bound_u256.lt(boundary)
             || boundary.eq(&ProofOfWorkProblem::NO_BOUNDARY)
     }
+
+    /// Check `nonce` against `share_boundary` instead of the block boundary
+    /// and return the result together with the quality of the hash. Mining
+    /// pools use a boundary easier than `self.boundary` to account partial
+    /// proofs; whether the block itself is valid is still decided by
+    /// `validate_with`. `lower_bound_bits` must match the mining config.
+    pub fn meets_share_target(
+        &self, nonce: &U256, share_boundary: &U256, lower_bound_bits: u32,
+    ) -> (bool, U256) {
+        let hash = compute(nonce, &self.block_hash);
+        (
+            Self::validate_hash_against_boundary_with(
+                &hash,
+                nonce,
+                share_boundary,
+                lower_bound_bits,
+            ),
+            pow_hash_to_quality_with(&hash, nonce, lower_bound_bits),
+        )
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -47,6 +82,34 @@ pub struct ProofOfWorkSolution {
     pub nonce: U256,
 }
 
+/// How the target difficulty of a period is derived from the blocks of the
+/// previous periods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeriveMallocSizeOf)]
+pub enum DifficultyAdjustmentAlgorithm {
+    /// The block rate over the last period only. This is what mainnet uses.
+    Windowed,
+    /// An exponential moving average of the targets observed in each of the
+    /// last `periods` periods, where each newer period is weighted by
+    /// `alpha_numerator / alpha_denominator` against the average of the
+    /// older ones. Meant for experimental and test chains.
+    Ema {
+        periods: u64,
+        alpha_numerator: u64,
+        alpha_denominator: u64,
+    },
+}
+
+/// The blocks of one adjustment period, as input to the difficulty
+/// adjustment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdjustmentPeriodStats {
+    pub block_count: u64,
+    /// In seconds.
+    pub timespan: u64,
+    /// The difficulty of the pivot block at the end of the period.
+    pub difficulty: U256,
+}
+
 #[derive(Debug, Clone, DeriveMallocSizeOf)]
 pub struct ProofOfWorkConfig {
     pub test_mode: bool,
@@ -57,15 +120,132 @@ pub struct ProofOfWorkConfig {
     pub stratum_listen_addr: String,
     pub stratum_port: u16,
     pub stratum_secret: Option<H256>,
+    /// The number of nonce bits used for the lower bound of the PoW. Mining
+    /// and validation must agree on it, so `VerificationConfig` has to use
+    /// the same value.
+    pub lower_bound_bits: u32,
+    /// Each difficulty adjustment moves the difficulty by the fraction
+    /// `damping_numerator / damping_denominator` of the way towards the
+    /// target, see `target_difficulty`.
+    pub damping_numerator: u64,
+    pub damping_denominator: u64,
+    /// Each difficulty adjustment stays within `[(1-1/N)*D, (1+1/N)*D]` of
+    /// the old difficulty D for this factor N, see `get_adjustment_bound`.
+    pub adjustment_factor: u64,
+    pub adjustment_algorithm: DifficultyAdjustmentAlgorithm,
+}
+
+/// A `ProofOfWorkConfig` rejected by `ProofOfWorkConfig::try_new`.
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub enum PowConfigError {
+    /// `difficulty_to_boundary` panics on a zero difficulty.
+    ZeroInitialDifficulty,
+    /// Stratum is enabled but the listen address isn't an IP address.
+    InvalidStratumAddress(String),
+    /// Stratum is enabled on port 0.
+    ZeroStratumPort,
+    ZeroAdjustmentPeriod,
+    /// `nonce_to_lower_bound_with` can't take 256 or more bits of a `U256`
+    /// nonce.
+    InvalidLowerBoundBits(u32),
+}
+
+impl fmt::Display for PowConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowConfigError::ZeroInitialDifficulty => {
+                write!(f, "Initial difficulty must be non-zero")
+            }
+            PowConfigError::InvalidStratumAddress(addr) => {
+                write!(f, "Invalid stratum listen address {:?}", addr)
+            }
+            PowConfigError::ZeroStratumPort => {
+                write!(f, "Stratum port must be non-zero")
+            }
+            PowConfigError::ZeroAdjustmentPeriod => {
+                write!(f, "Difficulty adjustment period must be non-zero")
+            }
+            PowConfigError::InvalidLowerBoundBits(bits) => {
+                write!(f, "Lower bound bits {} must be less than 256", bits)
+            }
+        }
+    }
+}
+
+impl error::Error for PowConfigError {
+    fn description(&self) -> &str { "PoW config error" }
 }
 
 impl ProofOfWorkConfig {
+    /// Panics on the configurations rejected by `try_new`.
     pub fn new(
         test_mode: bool, use_stratum: bool, initial_difficulty: Option<u64>,
         stratum_listen_addr: String, stratum_port: u16,
-        stratum_secret: Option<H256>,
+        stratum_secret: Option<H256>, adjustment_factor: Option<u64>,
+    ) -> Self
+    {
+        Self::try_new(
+            test_mode,
+            use_stratum,
+            initial_difficulty,
+            stratum_listen_addr,
+            stratum_port,
+            stratum_secret,
+            adjustment_factor,
+        )
+        .unwrap()
+    }
+
+    /// Same as `new`, but a misconfiguration is returned as an error at
+    /// startup rather than panicking later in the difficulty computation
+    /// or when starting stratum.
+    pub fn try_new(
+        test_mode: bool, use_stratum: bool, initial_difficulty: Option<u64>,
+        stratum_listen_addr: String, stratum_port: u16,
+        stratum_secret: Option<H256>, adjustment_factor: Option<u64>,
+    ) -> Result<Self, PowConfigError>
+    {
+        let config = Self::new_unchecked(
+            test_mode,
+            use_stratum,
+            initial_difficulty,
+            stratum_listen_addr,
+            stratum_port,
+            stratum_secret,
+            adjustment_factor,
+        );
+        if config.initial_difficulty == 0 {
+            return Err(PowConfigError::ZeroInitialDifficulty);
+        }
+        if config.use_stratum {
+            if config.stratum_listen_addr.parse::<IpAddr>().is_err() {
+                return Err(PowConfigError::InvalidStratumAddress(
+                    config.stratum_listen_addr,
+                ));
+            }
+            if config.stratum_port == 0 {
+                return Err(PowConfigError::ZeroStratumPort);
+            }
+        }
+        if config.difficulty_adjustment_epoch_period == 0 {
+            return Err(PowConfigError::ZeroAdjustmentPeriod);
+        }
+        if config.lower_bound_bits >= 256 {
+            return Err(PowConfigError::InvalidLowerBoundBits(
+                config.lower_bound_bits,
+            ));
+        }
+        Ok(config)
+    }
+
+    fn new_unchecked(
+        test_mode: bool, use_stratum: bool, initial_difficulty: Option<u64>,
+        stratum_listen_addr: String, stratum_port: u16,
+        stratum_secret: Option<H256>, adjustment_factor: Option<u64>,
     ) -> Self
     {
+        let adjustment_factor =
+            adjustment_factor.unwrap_or(DIFFICULTY_ADJUSTMENT_FACTOR as u64);
         if test_mode {
             ProofOfWorkConfig {
                 test_mode,
@@ -76,6 +256,11 @@ impl ProofOfWorkConfig {
                 stratum_listen_addr,
                 stratum_port,
                 stratum_secret,
+                lower_bound_bits: POW_LOWER_BOUND_BITS,
+                damping_numerator: DIFFICULTY_ADJUSTMENT_DAMPING_NUMERATOR,
+                damping_denominator: DIFFICULTY_ADJUSTMENT_DAMPING_DENOMINATOR,
+                adjustment_factor,
+                adjustment_algorithm: DifficultyAdjustmentAlgorithm::Windowed,
             }
         } else {
             ProofOfWorkConfig {
@@ -88,10 +273,20 @@ impl ProofOfWorkConfig {
                 stratum_listen_addr,
                 stratum_port,
                 stratum_secret,
+                lower_bound_bits: POW_LOWER_BOUND_BITS,
+                damping_numerator: DIFFICULTY_ADJUSTMENT_DAMPING_NUMERATOR,
+                damping_denominator: DIFFICULTY_ADJUSTMENT_DAMPING_DENOMINATOR,
+                adjustment_factor,
+                adjustment_algorithm: DifficultyAdjustmentAlgorithm::Windowed,
             }
         }
     }
 
+    /// The difficulty which would have produced blocks at the target rate in
+    /// the last period, moved from `cur_difficulty` by the damping fraction
+    /// of the way towards it, i.e. `cur + damping * (target - cur)`. A zero
+    /// damping denominator is treated as no damping. The adjustment bound is
+    /// applied by the caller.
     pub fn target_difficulty(
         &self, block_count: u64, timespan: u64, cur_difficulty: &U256,
     ) -> U256 {
@@ -99,11 +294,24 @@ impl ProofOfWorkConfig {
             return self.initial_difficulty.into();
         }
 
-        let target = (U512::from(*cur_difficulty)
-            * U512::from(self.block_generation_period)
-            // - 1 for unbiased estimation, like stdvar
-            * U512::from(block_count - 1))
-            / (U512::from(timespan) * U512::from(1000000));
+        let cur = U512::from(*cur_difficulty);
+        let mut target = self.observed_target(block_count, timespan, &cur);
+        if self.damping_denominator != 0
+            && self.damping_numerator != self.damping_denominator
+        {
+            let numerator = U512::from(self.damping_numerator);
+            let denominator = U512::from(self.damping_denominator);
+            target = if target >= cur {
+                cur + (target - cur) * numerator / denominator
+            } else {
+                let step = (cur - target) * numerator / denominator;
+                if step >= cur {
+                    U512::one()
+                } else {
+                    cur - step
+                }
+            };
+        }
         if target.is_zero() {
             return 1.into();
         }
@@ -113,10 +321,86 @@ impl ProofOfWorkConfig {
         U256::try_from(target).unwrap()
     }
 
+    /// The difficulty which would have produced `block_count` blocks at the
+    /// target rate in `timespan`, given that they were produced with
+    /// `cur_difficulty`. Requires a non-zero timespan and at least 2 blocks.
+    fn observed_target(
+        &self, block_count: u64, timespan: u64, cur_difficulty: &U512,
+    ) -> U512 {
+        // The product has at most 256 + 64 + 64 bits, so it can't overflow.
+        let target = (cur_difficulty
+            * U512::from(self.block_generation_period)
+            // - 1 for unbiased estimation, like stdvar
+            * U512::from(block_count - 1))
+            / (U512::from(timespan) * U512::from(1000000));
+        if target.is_zero() {
+            U512::one()
+        } else {
+            target
+        }
+    }
+
+    /// The target difficulty in `DifficultyAdjustmentAlgorithm::Ema` mode.
+    /// `history` holds the last periods, newest first. Starting from the
+    /// target observed in the oldest period, the average is moved by the
+    /// fraction `alpha_numerator / alpha_denominator` of the way towards the
+    /// target observed in each newer period. Periods with a zero timespan or
+    /// fewer than 2 blocks carry no information and are skipped. The
+    /// adjustment bound is applied by the caller.
+    pub fn ema_target_difficulty(
+        &self, history: &[AdjustmentPeriodStats], alpha_numerator: u64,
+        alpha_denominator: u64,
+    ) -> U256
+    {
+        if self.test_mode {
+            return self.initial_difficulty.into();
+        }
+        let mut ema: Option<U512> = None;
+        for period in history.iter().rev() {
+            if period.timespan == 0 || period.block_count <= 1 {
+                continue;
+            }
+            let observed = self.observed_target(
+                period.block_count,
+                period.timespan,
+                &U512::from(period.difficulty),
+            );
+            ema = Some(match ema {
+                None => observed,
+                // A zero denominator is treated as no averaging, like the
+                // damping fraction.
+                Some(_) if alpha_denominator == 0 => observed,
+                Some(ema) => {
+                    let numerator = U512::from(alpha_numerator);
+                    let denominator = U512::from(alpha_denominator);
+                    if observed >= ema {
+                        ema + (observed - ema) * numerator / denominator
+                    } else {
+                        let step = (ema - observed) * numerator / denominator;
+                        if step >= ema {
+                            U512::one()
+                        } else {
+                            ema - step
+                        }
+                    }
+                }
+            });
+        }
+        match ema {
+            None => self.initial_difficulty.into(),
+            Some(ema) if ema.is_zero() => 1.into(),
+            Some(ema) => {
+                U256::try_from(ema).unwrap_or_else(|_| U256::max_value())
+            }
+        }
+    }
+
+    /// The range the difficulty can move to from `diff` in one adjustment.
+    /// A zero `adjustment_factor` is treated as 1.
     pub fn get_adjustment_bound(&self, diff: U256) -> (U256, U256) {
-        let adjustment = diff / DIFFICULTY_ADJUSTMENT_FACTOR;
+        let adjustment = diff / U256::from(self.adjustment_factor.max(1));
         let mut min_diff = diff - adjustment;
-        let mut max_diff = diff + adjustment;
+        let mut max_diff = diff.saturating_add(adjustment);
         let initial_diff: U256 = self.initial_difficulty.into();
 
         if min_diff < initial_diff {
@@ -129,26 +413,98 @@ impl ProofOfWorkConfig {
 
         (min_diff, max_diff)
     }
+
+    /// The estimated network hashrate in hashes per second. A block takes
+    /// `difficulty` hashes in expectation and `block_generation_period` is
+    /// in microseconds, so this is
+    /// `difficulty * 1000000 / block_generation_period`, saturated at
+    /// `U256::MAX`. A zero period gives `U256::MAX`.
+    pub fn estimated_hashrate(&self, difficulty: &U256) -> U256 {
+        if self.block_generation_period == 0 {
+            return U256::max_value();
+        }
+        let hashrate = U512::from(*difficulty) * U512::from(1000000)
+            / U512::from(self.block_generation_period);
+        U256::try_from(hashrate).unwrap_or_else(|_| U256::max_value())
+    }
+
+    /// The inverse of `estimated_hashrate`: the difficulty at which a
+    /// network of `hashrate` hashes per second produces a block every
+    /// `block_generation_period` microseconds, i.e.
+    /// `hashrate * block_generation_period / 1000000`. The result is at
+    /// least 1 and saturated at `U256::MAX`.
+    pub fn difficulty_for_target_hashrate(&self, hashrate: &U256) -> U256 {
+        let difficulty = U512::from(*hashrate)
+            * U512::from(self.block_generation_period)
+            / U512::from(1000000);
+        if difficulty.is_zero() {
+            return U256::one();
+        }
+        U256::try_from(difficulty).unwrap_or_else(|_| U256::max_value())
+    }
+
+    /// Categorize the trend of the difficulties of consecutive adjustment
+    /// periods, oldest first. The least squares slope across the samples
+    /// gives the net change over the whole window, which is considered stable
+    /// when it stays within the bound of a single adjustment, i.e. `1 /
+    /// adjustment_factor` of the mean difficulty. Fewer than two samples are
+    /// always stable.
+    pub fn difficulty_trend(&self, difficulties: &[U256]) -> DifficultyTrend {
+        let n = difficulties.len();
+        if n < 2 {
+            return DifficultyTrend::Stable;
+        }
+        let samples: Vec<f64> = difficulties.iter().map(u256_to_f64).collect();
+        let mean_x = (n - 1) as f64 / 2.0;
+        let mean_y = samples.iter().sum::<f64>() / n as f64;
+        let mut covariance = 0f64;
+        let mut variance = 0f64;
+        for (i, y) in samples.iter().enumerate() {
+            let dx = i as f64 - mean_x;
+            covariance += dx * (y - mean_y);
+            variance += dx * dx;
+        }
+        let net_change = covariance / variance * (n - 1) as f64;
+        let tolerance = mean_y / self.adjustment_factor as f64;
+        if net_change > tolerance {
+            DifficultyTrend::Rising
+        } else if net_change < -tolerance {
+            DifficultyTrend::Falling
+        } else {
+            DifficultyTrend::Stable
+        }
+    }
 }
 
 // We will use the top 128 bits (excluding the highest bit) to be the lower
 // bound of our PoW. The rationale is to provide a solution for block
 // withholding attack among mining pools.
 pub fn nonce_to_lower_bound(nonce: &U256) -> U256 {
-    let mut buf = [0u8; 32];
-    nonce.to_big_endian(&mut buf[..]);
-    for i in 16..32 {
-        buf[i] = 0;
+    nonce_to_lower_bound_with(nonce, POW_LOWER_BOUND_BITS)
+}
+
+// Use the `lower_bound_bits` bits right below the highest bit of the nonce
+// as the lower bound of our PoW, and zero the others. With the default of 127
+// bits this is the top 128 bits excluding the highest bit.
+pub fn nonce_to_lower_bound_with(nonce: &U256, lower_bound_bits: u32) -> U256 {
+    debug_assert!(lower_bound_bits < 256);
+    if lower_bound_bits == 0 {
+        return U256::zero();
     }
-    buf[0] = buf[0] & 0x7f;
-    // Note that U256::from assumes big_endian of the bytes
-    let lower_bound = U256::from(buf);
-    lower_bound
+    let mask = ((U256::one() << lower_bound_bits as usize) - U256::one())
+        << (255 - lower_bound_bits) as usize;
+    *nonce & mask
 }
 
 pub fn pow_hash_to_quality(hash: &H256, nonce: &U256) -> U256 {
+    pow_hash_to_quality_with(hash, nonce, POW_LOWER_BOUND_BITS)
+}
+
+pub fn pow_hash_to_quality_with(
+    hash: &H256, nonce: &U256, lower_bound_bits: u32,
+) -> U256 {
     let hash_as_uint = BigEndianHash::into_uint(hash);
-    let lower_bound = nonce_to_lower_bound(nonce);
+    let lower_bound = nonce_to_lower_bound_with(nonce, lower_bound_bits);
     let (against_bound_u256, _) = hash_as_uint.overflowing_sub(lower_bound);
     if against_bound_u256.eq(&U256::MAX) {
         U256::one()
@@ -179,6 +535,103 @@ pub fn difficulty_to_boundary(difficulty: &U256) -> U256 {
     }
 }
 
+/// Convert the current and the next difficulty to their boundaries, to show
+/// how much the mining target shifts with a pending difficulty adjustment. A
+/// difficulty of 1 maps to `ProofOfWorkProblem::NO_BOUNDARY` on either side.
+pub fn preview_boundary_change(current: &U256, next: &U256) -> (U256, U256) {
+    (difficulty_to_boundary(current), difficulty_to_boundary(next))
+}
+
+/// How many times the work of difficulty `a` the work of difficulty `b` is,
+/// i.e. `a / b`, since the expected number of hashes to meet a difficulty is
+/// the difficulty itself. Returns infinity for a zero `b`, or 1 if both are
+/// zero.
+pub fn work_ratio(a: &U256, b: &U256) -> f64 {
+    if b.is_zero() {
+        return if a.is_zero() { 1.0 } else { std::f64::INFINITY };
+    }
+    u256_to_f64(a) / u256_to_f64(b)
+}
+
+/// Linearly interpolate between two difficulties, i.e. `prev + (next - prev)
+/// * fraction_numerator / fraction_denominator`. The fraction is clamped to
+/// [0, 1], and a zero denominator is treated as a fraction of 1. The
+/// computation is done in U512 so it never overflows.
+pub fn interpolate_difficulty(
+    prev: &U256, next: &U256, fraction_numerator: u64,
+    fraction_denominator: u64,
+) -> U256
+{
+    if fraction_denominator == 0 || fraction_numerator >= fraction_denominator
+    {
+        return *next;
+    }
+    let numerator = U512::from(fraction_numerator);
+    let denominator = U512::from(fraction_denominator);
+    let prev = U512::from(*prev);
+    let next = U512::from(*next);
+    let interpolated = if next >= prev {
+        prev + (next - prev) * numerator / denominator
+    } else {
+        prev - (prev - next) * numerator / denominator
+    };
+    // The result always lies between `prev` and `next`, so it fits in U256.
+    U256::try_from(interpolated).unwrap()
+}
+
+/// Estimate the expected number of seconds to find a block at `difficulty`
+/// with `hashrate_per_sec` hashes per second. The lower bound derived from
+/// the nonce is subtracted with wrap-around in `pow_hash_to_quality`, so the
+/// value checked against the boundary stays uniformly distributed over the
+/// full 256-bit space and every hash succeeds with probability
+/// `1 / difficulty`. The expected number of hashes is thus `difficulty`.
+/// Returns infinity for a zero hashrate.
+pub fn expected_seconds_to_block(
+    difficulty: &U256, hashrate_per_sec: &U256,
+) -> f64 {
+    if hashrate_per_sec.is_zero() {
+        return std::f64::INFINITY;
+    }
+    u256_to_f64(difficulty) / u256_to_f64(hashrate_per_sec)
+}
+
+fn u256_to_f64(x: &U256) -> f64 {
+    // Fold the little-endian 64-bit words starting from the most significant.
+    x.0.iter().rev().fold(0f64, |acc, word| {
+        acc * 18446744073709551616f64 + *word as f64
+    })
+}
+
+/// Compute the median of the differences between consecutive `timestamps`,
+/// which is robust against a few manipulated timestamps skewing the
+/// timespan. A timestamp earlier than its predecessor counts as a difference
+/// of zero. For an even number of differences the two middle values are
+/// averaged, rounding down. Returns `None` for fewer than two timestamps.
+pub fn median_block_time(timestamps: &[u64]) -> Option<u64> {
+    if timestamps.len() < 2 {
+        return None;
+    }
+    let mut intervals: Vec<u64> = timestamps
+        .windows(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]))
+        .collect();
+    intervals.sort_unstable();
+    let mid = intervals.len() / 2;
+    if intervals.len() % 2 == 1 {
+        Some(intervals[mid])
+    } else {
+        let (lower, upper) = (intervals[mid - 1], intervals[mid]);
+        Some(lower + (upper - lower) / 2)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DifficultyTrend {
+    Rising,
+    Falling,
+    Stable,
+}
+
 /// Compute [2^256 / x], where x >= 2 and x < 2^256.
 pub fn compute_inv_x_times_2_pow_256_floor(x: &U256) -> U256 {
     let (div, modular) = U256::MAX.clone().div_mod(x.clone());
@@ -190,10 +643,25 @@ pub fn compute_inv_x_times_2_pow_256_floor(x: &U256) -> U256 {
 }
 
 pub fn compute(nonce: &U256, block_hash: &H256) -> H256 {
-    let mut buf = [0u8; 64];
-    for i in 0..32 {
-        buf[i] = block_hash[i];
-    }
+    let mut scratch = [0u8; 64];
+    compute_into(nonce, block_hash, &mut scratch)
+}
+
+/// Same as `compute`, with the keccak buffer provided by the caller so that
+/// hot loops can reuse it across calls. The content of `scratch` on entry
+/// doesn't matter.
+pub fn compute_into(
+    nonce: &U256, block_hash: &H256, scratch: &mut [u8; 64],
+) -> H256 {
+    scratch[..32].copy_from_slice(block_hash.as_bytes());
+    compute_with_block_hash_set(nonce, block_hash, scratch)
+}
+
+/// `compute` with the first half of `buf` already holding the block hash,
+/// so that only the nonce bytes are written.
+fn compute_with_block_hash_set(
+    nonce: &U256, block_hash: &H256, buf: &mut [u8; 64],
+) -> H256 {
     nonce.to_little_endian(&mut buf[32..64]);
     let intermediate = keccak(&buf[..]);
     let mut tmp = [0u8; 32];
@@ -206,15 +674,168 @@ pub fn compute(nonce: &U256, block_hash: &H256) -> H256 {
 pub fn validate(
     problem: &ProofOfWorkProblem, solution: &ProofOfWorkSolution,
 ) -> bool {
+    validate_with(problem, solution, POW_LOWER_BOUND_BITS)
+}
+
+pub fn validate_with(
+    problem: &ProofOfWorkProblem, solution: &ProofOfWorkSolution,
+    lower_bound_bits: u32,
+) -> bool
+{
     let nonce = solution.nonce;
     let hash = compute(&nonce, &problem.block_hash);
-    ProofOfWorkProblem::validate_hash_against_boundary(
+    ProofOfWorkProblem::validate_hash_against_boundary_with(
         &hash,
         &nonce,
         &problem.boundary,
+        lower_bound_bits,
     )
 }
 
+/// Validate each of `nonces` against the same problem, e.g. for a burst of
+/// shares submitted to a mining pool. The block hash is copied into the
+/// keccak buffer once and only the nonce bytes change per nonce.
+pub fn validate_many(
+    problem: &ProofOfWorkProblem, nonces: &[U256], lower_bound_bits: u32,
+) -> Vec<bool> {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(problem.block_hash.as_bytes());
+    nonces
+        .iter()
+        .map(|nonce| {
+            let hash = compute_with_block_hash_set(
+                nonce,
+                &problem.block_hash,
+                &mut buf,
+            );
+            ProofOfWorkProblem::validate_hash_against_boundary_with(
+                &hash,
+                nonce,
+                &problem.boundary,
+                lower_bound_bits,
+            )
+        })
+        .collect()
+}
+
+/// Verification of PoW solutions, so that callers holding a
+/// `Box<dyn PowVerifier>` can have PoW stubbed out in unit tests.
+pub trait PowVerifier: Send + Sync {
+    fn verify(
+        &self, problem: &ProofOfWorkProblem, solution: &ProofOfWorkSolution,
+    ) -> bool;
+}
+
+/// Verifies solutions with `validate`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealPowVerifier;
+
+impl PowVerifier for RealPowVerifier {
+    fn verify(
+        &self, problem: &ProofOfWorkProblem, solution: &ProofOfWorkSolution,
+    ) -> bool {
+        validate(problem, solution)
+    }
+}
+
+/// Accepts any solution.
+#[cfg(test)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MockPowVerifier;
+
+#[cfg(test)]
+impl PowVerifier for MockPowVerifier {
+    fn verify(
+        &self, _problem: &ProofOfWorkProblem, _solution: &ProofOfWorkSolution,
+    ) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum PowError {
+    /// The difficulty of the problem is below the network floor.
+    DifficultyBelowMinimum { min: U256, found: U256 },
+    /// The nonce doesn't meet the boundary of the problem.
+    InvalidSolution { nonce: U256, boundary: U256 },
+    /// The header of the block isn't in `BlockDataManager`.
+    MissingHeader(H256),
+    /// The block set of the epoch isn't in the db.
+    MissingEpoch(u64),
+}
+
+impl fmt::Display for PowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PowError::DifficultyBelowMinimum { min, found } => write!(
+                f,
+                "Difficulty {} is below the minimum difficulty {}",
+                found, min
+            ),
+            PowError::InvalidSolution { nonce, boundary } => write!(
+                f,
+                "Nonce {} doesn't meet the PoW boundary {}",
+                nonce, boundary
+            ),
+            PowError::MissingHeader(hash) => {
+                write!(f, "Missing block header {:?}", hash)
+            }
+            PowError::MissingEpoch(epoch) => {
+                write!(f, "Missing block set of epoch {}", epoch)
+            }
+        }
+    }
+}
+
+impl error::Error for PowError {
+    fn description(&self) -> &str { "PoW error" }
+}
+
+/// Validate the PoW of a block: the difficulty of `problem` must be at least
+/// `min_difficulty` and `solution` must meet its boundary. The difficulty is
+/// checked first, since it doesn't need any hashing.
+pub fn validate_block_pow(
+    problem: &ProofOfWorkProblem, solution: &ProofOfWorkSolution,
+    min_difficulty: &U256, lower_bound_bits: u32,
+) -> Result<(), PowError>
+{
+    if problem.difficulty < *min_difficulty {
+        return Err(PowError::DifficultyBelowMinimum {
+            min: *min_difficulty,
+            found: problem.difficulty,
+        });
+    }
+    if !validate_with(problem, solution, lower_bound_bits) {
+        return Err(PowError::InvalidSolution {
+            nonce: solution.nonce,
+            boundary: problem.boundary,
+        });
+    }
+    Ok(())
+}
+
+/// Search for the smallest nonce whose PoW hash for `block_hash` is within
+/// `boundary`, to construct valid test blocks deterministically. Returns
+/// `None` if no nonce below `MIN_SATISFYING_NONCE_SEARCH_LIMIT` satisfies the
+/// boundary, so it's only practical for low difficulties.
+#[cfg(test)]
+pub fn min_satisfying_nonce(
+    block_hash: &H256, boundary: &U256,
+) -> Option<U256> {
+    (0..MIN_SATISFYING_NONCE_SEARCH_LIMIT)
+        .map(U256::from)
+        .find(|nonce| {
+            ProofOfWorkProblem::validate_hash_against_boundary(
+                &compute(nonce, block_hash),
+                nonce,
+                boundary,
+            )
+        })
+}
+
+#[cfg(test)]
+const MIN_SATISFYING_NONCE_SEARCH_LIMIT: u64 = 1 << 20;
+
 /// This function computes the target difficulty of the next period
 /// based on the current period. `cur_hash` should be the hash of
 /// the block at the current period upper boundary and it must have been
@@ -234,9 +855,35 @@ where
         return target_diff;
     }
 
-    let mut cur_header = data_man
+    let target_diff = compute_target_difficulty(
+        data_man,
+        pow_config,
+        cur_hash,
+        num_blocks_in_epoch,
+    )
+    .expect("Must already in BlockDataManager block_header");
+
+    // Caching the computed target difficulty of this period.
+    data_man
+        .target_difficulty_manager
+        .set(*cur_hash, target_diff);
+
+    target_diff
+}
+
+/// The uncached computation of `target_difficulty`. Returns
+/// `PowError::MissingHeader` if a header of the period isn't in
+/// BlockDataManager.
+fn compute_target_difficulty<F>(
+    data_man: &BlockDataManager, pow_config: &ProofOfWorkConfig,
+    cur_hash: &H256, num_blocks_in_epoch: F,
+) -> Result<U256, PowError>
+where
+    F: Fn(&H256) -> usize,
+{
+    let cur_header = data_man
         .block_header_by_hash(cur_hash)
-        .expect("Must already in BlockDataManager block_header");
+        .ok_or(PowError::MissingHeader(*cur_hash))?;
     let epoch = cur_header.height();
     assert_ne!(epoch, 0);
     debug_assert!(
@@ -245,100 +892,284 @@ where
                 * pow_config.difficulty_adjustment_epoch_period
     );
 
-    let mut cur = cur_hash.clone();
-    let cur_difficulty = cur_header.difficulty().clone();
+    let (period, mut period_start) = collect_adjustment_period_stats(
+        data_man,
+        pow_config,
+        cur_hash,
+        &num_blocks_in_epoch,
+    )?;
+    let cur_difficulty = period.difficulty;
+
+    let mut target_diff = match pow_config.adjustment_algorithm {
+        DifficultyAdjustmentAlgorithm::Windowed => pow_config
+            .target_difficulty(
+                period.block_count,
+                period.timespan,
+                &cur_difficulty,
+            ),
+        DifficultyAdjustmentAlgorithm::Ema {
+            periods,
+            alpha_numerator,
+            alpha_denominator,
+        } => {
+            // The walk stops at genesis.
+            let available =
+                epoch / pow_config.difficulty_adjustment_epoch_period;
+            let mut history = vec![period];
+            for _ in 1..periods.min(available) {
+                let (older, older_start) = collect_adjustment_period_stats(
+                    data_man,
+                    pow_config,
+                    &period_start,
+                    &num_blocks_in_epoch,
+                )?;
+                history.push(older);
+                period_start = older_start;
+            }
+            pow_config.ema_target_difficulty(
+                &history,
+                alpha_numerator,
+                alpha_denominator,
+            )
+        }
+    };
+
+    let (lower, upper) = pow_config.get_adjustment_bound(cur_difficulty);
+    if target_diff > upper {
+        target_diff = upper;
+    }
+    if target_diff < lower {
+        target_diff = lower;
+    }
+
+    Ok(target_diff)
+}
+
+/// Collect the total block count and the timespan of the period ending with
+/// the pivot block `period_end`. Also returns the hash of the pivot block
+/// right before the period, which ends the previous period.
+fn collect_adjustment_period_stats<F>(
+    data_man: &BlockDataManager, pow_config: &ProofOfWorkConfig,
+    period_end: &H256, num_blocks_in_epoch: &F,
+) -> Result<(AdjustmentPeriodStats, H256), PowError>
+where
+    F: Fn(&H256) -> usize,
+{
+    let mut cur_header = data_man
+        .block_header_by_hash(period_end)
+        .ok_or(PowError::MissingHeader(*period_end))?;
+    let mut cur = *period_end;
+    let difficulty = *cur_header.difficulty();
     let mut block_count = 0 as u64;
     let max_time = cur_header.timestamp();
     let mut min_time = 0;
 
-    // Collect the total block count and the timespan in the current period
     for _ in 0..pow_config.difficulty_adjustment_epoch_period {
         block_count += num_blocks_in_epoch(&cur) as u64;
         cur = cur_header.parent_hash().clone();
-        cur_header = data_man.block_header_by_hash(&cur).unwrap();
+        cur_header = data_man
+            .block_header_by_hash(&cur)
+            .ok_or(PowError::MissingHeader(cur))?;
         if cur_header.timestamp() != 0 {
             min_time = cur_header.timestamp();
         }
         assert!(max_time >= min_time);
     }
 
-    let mut target_diff = pow_config.target_difficulty(
-        block_count,
-        max_time - min_time,
-        &cur_difficulty,
-    );
+    Ok((
+        AdjustmentPeriodStats {
+            block_count,
+            timespan: max_time - min_time,
+            difficulty,
+        },
+        cur,
+    ))
+}
 
-    let (lower, upper) = pow_config.get_adjustment_bound(cur_difficulty);
-    if target_diff > upper {
-        target_diff = upper;
+/// A period whose recorded difficulty differs from the recomputed one, found
+/// by `audit_difficulty_adjustments`.
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct DifficultyAuditEntry {
+    /// The height of the pivot block at the end of the previous period.
+    pub boundary_height: u64,
+    pub boundary_hash: H256,
+    /// The difficulty recomputed with the current `ProofOfWorkConfig`.
+    pub expected: U256,
+    /// The difficulty of the first pivot block of the period.
+    pub recorded: U256,
+}
+
+/// Recompute the difficulty adjustment at every period boundary of the pivot
+/// chain between `from_height` and `to_height` inclusive, and report the
+/// periods whose first pivot block records a difficulty differing from the
+/// recomputed one by more than `DIFFICULTY_AUDIT_TOLERANCE`. The cached
+/// target difficulties are bypassed. Boundaries whose next epoch isn't in
+/// the db yet are skipped.
+pub fn audit_difficulty_adjustments(
+    data_man: &BlockDataManager, pow_config: &ProofOfWorkConfig,
+    from_height: u64, to_height: u64,
+) -> Result<Vec<DifficultyAuditEntry>, PowError>
+{
+    let period = pow_config.difficulty_adjustment_epoch_period;
+    let pivot_hash = |height: u64| {
+        data_man
+            .all_epoch_set_hashes_from_db(height)
+            .and_then(|epoch_set| epoch_set.last().cloned())
+    };
+    // The pivot block comes last in its epoch set, so its epoch number is
+    // its height.
+    let num_blocks_in_epoch = |hash: &H256| {
+        data_man
+            .block_header_by_hash(hash)
+            .and_then(|header| {
+                data_man.all_epoch_set_hashes_from_db(header.height())
+            })
+            .map_or(0, |epoch_set| epoch_set.len())
+    };
+
+    let mut mismatches = Vec::new();
+    if period == 0 {
+        return Ok(mismatches);
     }
-    if target_diff < lower {
-        target_diff = lower;
+    let mut boundary_height = match from_height % period {
+        0 => from_height,
+        rem => from_height.saturating_add(period - rem),
+    };
+    // There is no adjustment before the end of the first period.
+    if boundary_height < period {
+        boundary_height = period;
     }
+    while boundary_height <= to_height {
+        let next_hash = match pivot_hash(boundary_height + 1) {
+            Some(hash) => hash,
+            None => break,
+        };
+        let boundary_hash = pivot_hash(boundary_height)
+            .ok_or(PowError::MissingEpoch(boundary_height))?;
+        let expected = compute_target_difficulty(
+            data_man,
+            pow_config,
+            &boundary_hash,
+            &num_blocks_in_epoch,
+        )?;
+        let recorded = *data_man
+            .block_header_by_hash(&next_hash)
+            .ok_or(PowError::MissingHeader(next_hash))?
+            .difficulty();
+        let deviation = if expected > recorded {
+            expected - recorded
+        } else {
+            recorded - expected
+        };
+        if deviation > U256::from(DIFFICULTY_AUDIT_TOLERANCE) {
+            mismatches.push(DifficultyAuditEntry {
+                boundary_height,
+                boundary_hash,
+                expected,
+                recorded,
+            });
+        }
+        boundary_height = match boundary_height.checked_add(period) {
+            Some(height) => height,
+            None => break,
+        };
+    }
+    Ok(mismatches)
+}
 
-    // Caching the computed target difficulty of this period.
-    data_man
-        .target_difficulty_manager
-        .set(*cur_hash, target_diff);
+/// The expected number of hashes to mine a block at `difficulty`.
+pub fn block_work(difficulty: &U256) -> U512 { U512::from(*difficulty) }
 
-    target_diff
+/// Sum the work of the blocks from `tip_hash` back to `ancestor_hash` along
+/// the parent chain, excluding the ancestor itself, so that the totals of two
+/// tips with the same ancestor can be compared. Returns
+/// `PowError::MissingHeader` if a header on the way isn't available, which
+/// includes walking past genesis when the ancestor isn't on the chain.
+pub fn cumulative_work_from_headers(
+    data_man: &BlockDataManager, tip_hash: &H256, ancestor_hash: &H256,
+) -> Result<U512, PowError> {
+    let mut total = U512::zero();
+    let mut cur = *tip_hash;
+    while cur != *ancestor_hash {
+        let header = data_man
+            .block_header_by_hash(&cur)
+            .ok_or(PowError::MissingHeader(cur))?;
+        total = total + block_work(header.difficulty());
+        cur = *header.parent_hash();
+    }
+    Ok(total)
 }
 
-//FIXME: make entries replaceable
-#[derive(DeriveMallocSizeOf)]
+/// The least recently used entries are evicted once the capacity is reached.
+/// An evicted entry is simply recomputed by `target_difficulty` on the next
+/// miss, so eviction never affects correctness.
 struct TargetDifficultyCacheInner {
-    cache: HashMap<H256, U256>,
+    cache: LruCache<H256, U256>,
 }
 
 impl TargetDifficultyCacheInner {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         TargetDifficultyCacheInner {
-            cache: Default::default(),
+            cache: LruCache::new(capacity),
         }
     }
 }
 
+impl MallocSizeOf for TargetDifficultyCacheInner {
+    // The lru crate doesn't expose its allocations, so count the entries
+    // only.
+    fn size_of(&self, _ops: &mut MallocSizeOfOps) -> usize {
+        self.cache.len() * mem::size_of::<(H256, U256)>()
+    }
+}
+
 struct TargetDifficultyCache {
-    inner: RwLock<TargetDifficultyCacheInner>,
+    inner: Mutex<TargetDifficultyCacheInner>,
 }
 
 impl MallocSizeOf for TargetDifficultyCache {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
-        self.inner.read().size_of(ops)
+        self.inner.lock().size_of(ops)
     }
 }
 
 impl TargetDifficultyCache {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         TargetDifficultyCache {
-            inner: RwLock::new(TargetDifficultyCacheInner::new()),
+            inner: Mutex::new(TargetDifficultyCacheInner::new(capacity)),
         }
     }
 
     pub fn get(&self, hash: &H256) -> Option<U256> {
-        let inner = self.inner.read();
+        let mut inner = self.inner.lock();
         inner.cache.get(hash).map(|diff| *diff)
     }
 
     pub fn set(&self, hash: H256, difficulty: U256) {
-        let mut inner = self.inner.write();
-        inner.cache.insert(hash, difficulty);
+        let mut inner = self.inner.lock();
+        inner.cache.put(hash, difficulty);
+    }
+
+    pub fn entries(&self) -> Vec<(H256, U256)> {
+        let inner = self.inner.lock();
+        inner.cache.iter().map(|(hash, diff)| (*hash, *diff)).collect()
     }
 }
 
 //FIXME: Add logic for persisting entries
 /// This is a data structure to cache the computed target difficulty
 /// of a adjustment period. Each element is indexed by the hash of
-/// the upper boundary block of the period.
+/// the upper boundary block of the period. At most `capacity` periods are
+/// cached.
 #[derive(DeriveMallocSizeOf)]
 pub struct TargetDifficultyManager {
     cache: TargetDifficultyCache,
 }
 
 impl TargetDifficultyManager {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         TargetDifficultyManager {
-            cache: TargetDifficultyCache::new(),
+            cache: TargetDifficultyCache::new(capacity),
         }
     }
 
@@ -347,4 +1178,937 @@ impl TargetDifficultyManager {
     pub fn set(&self, hash: H256, difficulty: U256) {
         self.cache.set(hash, difficulty);
     }
+
+    /// Snapshot all the cached target difficulties, sorted by the hash of
+    /// the upper boundary block of their period. The whole cache is copied
+    /// under its mutex, which `get` also takes exclusively to update the LRU
+    /// order, so this blocks lookups and is meant for offline analysis
+    /// rather than hot paths.
+    pub fn entries(&self) -> Vec<(H256, U256)> {
+        let mut entries = self.cache.entries();
+        entries.sort();
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block_data_manager::DbType, sync::utils::initialize_data_manager,
+    };
+    use primitives::BlockHeaderBuilder;
+    use std::{fs, sync::Arc};
+
+    #[test]
+    fn test_interpolate_difficulty() {
+        let prev = U256::from(100);
+        let next = U256::from(200);
+        assert_eq!(interpolate_difficulty(&prev, &next, 0, 4), prev);
+        assert_eq!(interpolate_difficulty(&prev, &next, 1, 4), 125.into());
+        assert_eq!(interpolate_difficulty(&prev, &next, 4, 4), next);
+        // Fraction is clamped to 1.
+        assert_eq!(interpolate_difficulty(&prev, &next, 5, 4), next);
+        assert_eq!(interpolate_difficulty(&prev, &next, 1, 0), next);
+        // Decreasing difficulty.
+        assert_eq!(interpolate_difficulty(&next, &prev, 1, 4), 175.into());
+        // No overflow at the top of the range.
+        assert_eq!(
+            interpolate_difficulty(&U256::MAX, &U256::zero(), 1, 2),
+            U256::MAX / 2 + 1
+        );
+        assert_eq!(
+            interpolate_difficulty(&U256::zero(), &U256::MAX, 1, 2),
+            U256::MAX / 2
+        );
+    }
+
+    #[test]
+    fn test_nonce_to_lower_bound_with_default_bits() {
+        // The lower bound used to be computed by zeroing the low 16 bytes and
+        // the highest bit of the nonce.
+        let legacy_lower_bound = |nonce: &U256| {
+            let mut buf = [0u8; 32];
+            nonce.to_big_endian(&mut buf[..]);
+            for i in 16..32 {
+                buf[i] = 0;
+            }
+            buf[0] = buf[0] & 0x7f;
+            U256::from(buf)
+        };
+        for nonce in &[
+            U256::zero(),
+            U256::one(),
+            U256::MAX,
+            U256::MAX >> 1,
+            U256::from(0x1234_5678u64) << 130,
+            U256::from_big_endian(&[0xa5u8; 32]),
+        ] {
+            assert_eq!(nonce_to_lower_bound(nonce), legacy_lower_bound(nonce));
+            assert_eq!(
+                nonce_to_lower_bound_with(nonce, POW_LOWER_BOUND_BITS),
+                legacy_lower_bound(nonce)
+            );
+        }
+        assert_eq!(nonce_to_lower_bound_with(&U256::MAX, 0), U256::zero());
+        assert_eq!(
+            nonce_to_lower_bound_with(&U256::MAX, 63),
+            (U256::MAX >> 193) << 192
+        );
+        assert_eq!(nonce_to_lower_bound_with(&U256::MAX, 255), U256::MAX >> 1);
+    }
+
+    #[test]
+    fn test_min_satisfying_nonce() {
+        let block_hash = H256::from_low_u64_be(1);
+        assert_eq!(
+            min_satisfying_nonce(&block_hash, &ProofOfWorkProblem::NO_BOUNDARY),
+            Some(U256::zero())
+        );
+
+        let problem = ProofOfWorkProblem::new(block_hash, U256::from(1000));
+        let nonce =
+            min_satisfying_nonce(&block_hash, &problem.boundary).unwrap();
+        assert!(validate(&problem, &ProofOfWorkSolution { nonce }));
+        for smaller in 0..nonce.low_u64() {
+            let smaller = U256::from(smaller);
+            assert!(!validate(
+                &problem,
+                &ProofOfWorkSolution { nonce: smaller }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_median_block_time() {
+        assert_eq!(median_block_time(&[]), None);
+        assert_eq!(median_block_time(&[100]), None);
+        assert_eq!(median_block_time(&[100, 105]), Some(5));
+        assert_eq!(median_block_time(&[100, 101, 104, 110]), Some(3));
+        assert_eq!(median_block_time(&[100, 101, 104, 110, 120]), Some(4));
+        // A manipulated timestamp doesn't move the median much.
+        assert_eq!(median_block_time(&[100, 102, 1000, 104, 106]), Some(2));
+        assert_eq!(
+            median_block_time(&[0, std::u64::MAX - 1, std::u64::MAX]),
+            Some(std::u64::MAX / 2)
+        );
+    }
+
+    #[test]
+    fn test_difficulty_trend() {
+        let new_config = |adjustment_factor| {
+            ProofOfWorkConfig::new(
+                false,
+                false,
+                None,
+                String::new(),
+                0,
+                None,
+                adjustment_factor,
+            )
+        };
+        let config = new_config(None);
+        let samples = |values: &[u64]| -> Vec<U256> {
+            values.iter().map(|v| U256::from(*v)).collect()
+        };
+        assert_eq!(config.difficulty_trend(&[]), DifficultyTrend::Stable);
+        assert_eq!(
+            config.difficulty_trend(&samples(&[1000])),
+            DifficultyTrend::Stable
+        );
+        assert_eq!(
+            config.difficulty_trend(&samples(&[1000, 1100, 1200, 1300])),
+            DifficultyTrend::Stable
+        );
+        assert_eq!(
+            config.difficulty_trend(&samples(&[1000, 1400, 1800, 2200])),
+            DifficultyTrend::Rising
+        );
+        assert_eq!(
+            config.difficulty_trend(&samples(&[2200, 1800, 1400, 1000])),
+            DifficultyTrend::Falling
+        );
+        // A single spike doesn't make a trend.
+        assert_eq!(
+            config.difficulty_trend(&samples(&[1000, 3000, 1000, 1000])),
+            DifficultyTrend::Stable
+        );
+        // Large difficulties don't overflow.
+        let huge = U256::MAX / 4;
+        assert_eq!(
+            config.difficulty_trend(&[huge, huge * 2, huge * 3]),
+            DifficultyTrend::Rising
+        );
+        // A larger factor tightens the bound of a single adjustment.
+        assert_eq!(
+            new_config(Some(10))
+                .difficulty_trend(&samples(&[1000, 1100, 1200, 1300])),
+            DifficultyTrend::Rising
+        );
+    }
+
+    #[test]
+    fn test_validate_block_pow() {
+        let block_hash = H256::from_low_u64_be(1);
+        let problem = ProofOfWorkProblem::new(block_hash, U256::from(1000));
+        let nonce =
+            min_satisfying_nonce(&block_hash, &problem.boundary).unwrap();
+        let solution = ProofOfWorkSolution { nonce };
+
+        assert_eq!(
+            validate_block_pow(
+                &problem,
+                &solution,
+                &U256::from(1000),
+                POW_LOWER_BOUND_BITS
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            validate_block_pow(
+                &problem,
+                &solution,
+                &U256::from(1001),
+                POW_LOWER_BOUND_BITS
+            ),
+            Err(PowError::DifficultyBelowMinimum {
+                min: U256::from(1001),
+                found: U256::from(1000),
+            })
+        );
+        // Nonces below the minimal satisfying one all fail.
+        let invalid = ProofOfWorkSolution {
+            nonce: nonce - U256::one(),
+        };
+        assert_eq!(
+            validate_block_pow(
+                &problem,
+                &invalid,
+                &U256::one(),
+                POW_LOWER_BOUND_BITS
+            ),
+            Err(PowError::InvalidSolution {
+                nonce: invalid.nonce,
+                boundary: problem.boundary,
+            })
+        );
+        // The solution is checked with the given lower bound bits.
+        for lower_bound_bits in &[0, 63, POW_LOWER_BOUND_BITS] {
+            assert_eq!(
+                validate_block_pow(
+                    &problem,
+                    &solution,
+                    &U256::one(),
+                    *lower_bound_bits
+                )
+                .is_ok(),
+                validate_with(&problem, &solution, *lower_bound_bits)
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimated_hashrate() {
+        let mut config = ProofOfWorkConfig::new(
+            false,
+            false,
+            None,
+            String::new(),
+            0,
+            None,
+            None,
+        );
+        // Two blocks per second.
+        assert_eq!(config.block_generation_period, 500000);
+        assert_eq!(
+            config.estimated_hashrate(&U256::from(5_000_000)),
+            U256::from(10_000_000)
+        );
+        assert_eq!(
+            config.difficulty_for_target_hashrate(&U256::from(10_000_000)),
+            U256::from(5_000_000)
+        );
+        assert_eq!(
+            config.estimated_hashrate(&U256::max_value()),
+            U256::max_value()
+        );
+        assert_eq!(config.difficulty_for_target_hashrate(&0.into()), 1.into());
+
+        config.block_generation_period = 2000000;
+        assert_eq!(config.estimated_hashrate(&U256::from(1000)), 500.into());
+        assert_eq!(
+            config.difficulty_for_target_hashrate(&U256::max_value()),
+            U256::max_value()
+        );
+        config.block_generation_period = 0;
+        assert_eq!(config.estimated_hashrate(&1.into()), U256::max_value());
+    }
+
+    #[test]
+    fn test_ema_difficulty_adjustment_hashrate_step() {
+        const BLOCK_COUNT: u64 = 1001;
+        const STEP_PERIOD: usize = 3;
+        const PERIODS: usize = 12;
+        let mut config = ProofOfWorkConfig::new(
+            false,
+            false,
+            None,
+            String::new(),
+            0,
+            None,
+            Some(1),
+        );
+        config.initial_difficulty = 1;
+        // The hashrate steps from 1M to 1.5M hashes per second, so the
+        // difficulty for a block every 0.5s steps from 500000 to 750000.
+        let simulate = |algorithm| {
+            let mut difficulty = U256::from(500_000);
+            let mut history = Vec::new();
+            let mut difficulties = vec![difficulty];
+            for period in 0..PERIODS {
+                let hashrate = if period < STEP_PERIOD {
+                    1_000_000
+                } else {
+                    1_500_000
+                };
+                let timespan = ((BLOCK_COUNT - 1) * difficulty.low_u64())
+                    / hashrate as u64;
+                history.insert(
+                    0,
+                    AdjustmentPeriodStats {
+                        block_count: BLOCK_COUNT,
+                        timespan,
+                        difficulty,
+                    },
+                );
+                let target = match algorithm {
+                    DifficultyAdjustmentAlgorithm::Windowed => config
+                        .target_difficulty(BLOCK_COUNT, timespan, &difficulty),
+                    DifficultyAdjustmentAlgorithm::Ema {
+                        periods,
+                        alpha_numerator,
+                        alpha_denominator,
+                    } => {
+                        history.truncate(periods as usize);
+                        config.ema_target_difficulty(
+                            &history,
+                            alpha_numerator,
+                            alpha_denominator,
+                        )
+                    }
+                };
+                let (lower, upper) = config.get_adjustment_bound(difficulty);
+                difficulty = target.max(lower).min(upper);
+                difficulties.push(difficulty);
+            }
+            difficulties
+        };
+        let within = |x: &U256, ideal: u64, percent: u64| {
+            let x = x.low_u64();
+            x * 100 >= ideal * (100 - percent)
+                && x * 100 <= ideal * (100 + percent)
+        };
+
+        let windowed = simulate(DifficultyAdjustmentAlgorithm::Windowed);
+        let ema = simulate(DifficultyAdjustmentAlgorithm::Ema {
+            periods: 4,
+            alpha_numerator: 1,
+            alpha_denominator: 2,
+        });
+        // Both are stable under a constant hashrate.
+        for (windowed, ema) in windowed.iter().zip(&ema).take(STEP_PERIOD + 1)
+        {
+            assert_eq!(*windowed, U256::from(500_000));
+            assert_eq!(*ema, U256::from(500_000));
+        }
+        // The windowed average jumps to the new difficulty in one period,
+        // the moving average blends it with the older periods.
+        assert!(within(&windowed[STEP_PERIOD + 1], 750_000, 1));
+        assert!(ema[STEP_PERIOD + 1] > 600_000.into());
+        assert!(ema[STEP_PERIOD + 1] < 650_000.into());
+        // It then converges as the older periods leave the window.
+        for i in STEP_PERIOD + 1..STEP_PERIOD + 4 {
+            assert!(ema[i] < windowed[i]);
+            assert!(ema[i + 1] > ema[i]);
+        }
+        assert!(within(windowed.last().unwrap(), 750_000, 1));
+        assert!(within(ema.last().unwrap(), 750_000, 2));
+    }
+
+    #[test]
+    fn test_pow_config_try_new() {
+        let try_new = |initial_difficulty, listen_addr: &str, port| {
+            ProofOfWorkConfig::try_new(
+                true,
+                true,
+                initial_difficulty,
+                listen_addr.into(),
+                port,
+                None,
+                None,
+            )
+        };
+        assert!(try_new(None, "127.0.0.1", 32525).is_ok());
+        assert_eq!(
+            try_new(Some(0), "127.0.0.1", 32525).unwrap_err(),
+            PowConfigError::ZeroInitialDifficulty
+        );
+        assert_eq!(
+            try_new(None, "", 32525).unwrap_err(),
+            PowConfigError::InvalidStratumAddress(String::new())
+        );
+        assert_eq!(
+            try_new(None, "127.0.0.1", 0).unwrap_err(),
+            PowConfigError::ZeroStratumPort
+        );
+        // The stratum settings are ignored when stratum is disabled.
+        assert!(ProofOfWorkConfig::try_new(
+            true,
+            false,
+            None,
+            String::new(),
+            0,
+            None,
+            None
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_adjustment_factor() {
+        let new_config = |adjustment_factor| {
+            ProofOfWorkConfig::new(
+                false,
+                false,
+                None,
+                String::new(),
+                0,
+                None,
+                adjustment_factor,
+            )
+        };
+        let diff = U256::from(100_000_000);
+        let config = new_config(None);
+        assert_eq!(
+            config.adjustment_factor,
+            DIFFICULTY_ADJUSTMENT_FACTOR as u64
+        );
+        assert_eq!(
+            config.get_adjustment_bound(diff),
+            (50_000_000.into(), 150_000_000.into())
+        );
+        assert_eq!(
+            new_config(Some(10)).get_adjustment_bound(diff),
+            (90_000_000.into(), 110_000_000.into())
+        );
+        // The lower bound is still clamped to the initial difficulty.
+        assert_eq!(
+            new_config(Some(1)).get_adjustment_bound(diff),
+            (INITIAL_DIFFICULTY.into(), 200_000_000.into())
+        );
+    }
+
+    #[test]
+    fn test_target_difficulty_extreme_inputs() {
+        let mut config = ProofOfWorkConfig::new(
+            false,
+            false,
+            None,
+            String::new(),
+            0,
+            None,
+            None,
+        );
+        // Normal inputs: 2 blocks per period of 1 second.
+        assert_eq!(
+            config.target_difficulty(3, 1, &U256::from(1000)),
+            U256::from(1000)
+        );
+        assert_eq!(
+            config.target_difficulty(std::u64::MAX, 1, &U256::MAX),
+            U256::MAX
+        );
+        // MAX * 500000 * (2^64 - 2) / ((2^64 - 1) * 10^6)
+        assert_eq!(
+            config.target_difficulty(std::u64::MAX, std::u64::MAX, &U256::MAX),
+            U256([0x7fff_ffff_ffff_ffff; 4])
+        );
+        // MAX * 500000 / ((2^64 - 1) * 10^6)
+        let slow = U256([1 << 63, 1 << 63, 1 << 63, 0]);
+        assert_eq!(
+            config.target_difficulty(2, std::u64::MAX, &U256::MAX),
+            slow
+        );
+        assert_eq!(
+            config.target_difficulty(std::u64::MAX, 1, &U256::one()),
+            U256::from(std::u64::MAX - 1) / 2
+        );
+
+        config.damping_numerator = 1;
+        config.damping_denominator = 2;
+        assert_eq!(
+            config.target_difficulty(std::u64::MAX, 1, &U256::MAX),
+            U256::MAX
+        );
+        assert_eq!(
+            config.target_difficulty(2, std::u64::MAX, &U256::MAX),
+            U256([1 << 62, 1 << 62, 1 << 62, 1 << 63])
+        );
+
+        let (min_diff, max_diff) = config.get_adjustment_bound(U256::MAX);
+        assert_eq!(min_diff, U256::MAX - U256::MAX / 2);
+        assert_eq!(max_diff, U256::MAX);
+    }
+
+    #[test]
+    fn test_compute_into() {
+        let mut scratch = [0xffu8; 64];
+        for i in 0..16u64 {
+            let nonce = U256::from(i) << 200;
+            let block_hash = H256::from_low_u64_be(i * 7);
+            assert_eq!(
+                compute_into(&nonce, &block_hash, &mut scratch),
+                compute(&nonce, &block_hash)
+            );
+        }
+        // Bit-identical to the original compute.
+        let nonce = U256::from(12345);
+        let block_hash = H256::from_low_u64_be(1);
+        let mut buf = [0u8; 64];
+        buf[..32].copy_from_slice(block_hash.as_bytes());
+        nonce.to_little_endian(&mut buf[32..64]);
+        let intermediate = keccak(&buf[..]);
+        let mut tmp = [0u8; 32];
+        for (i, byte) in tmp.iter_mut().enumerate() {
+            *byte = intermediate[i] ^ block_hash[i];
+        }
+        assert_eq!(compute(&nonce, &block_hash), keccak(tmp));
+    }
+
+    #[test]
+    fn test_validate_many() {
+        let block_hash = H256::from_low_u64_be(1);
+        let problem = ProofOfWorkProblem::new(block_hash, U256::from(1000));
+        let nonce =
+            min_satisfying_nonce(&block_hash, &problem.boundary).unwrap();
+        let nonces: Vec<U256> =
+            (0..64u64).map(|i| nonce + U256::from(i)).collect();
+        let expected = |lower_bound_bits| -> Vec<bool> {
+            nonces
+                .iter()
+                .map(|nonce| {
+                    validate_with(
+                        &problem,
+                        &ProofOfWorkSolution { nonce: *nonce },
+                        lower_bound_bits,
+                    )
+                })
+                .collect()
+        };
+        assert!(expected(POW_LOWER_BOUND_BITS)[0]);
+        assert!(expected(POW_LOWER_BOUND_BITS).contains(&false));
+        for lower_bound_bits in &[0, 63, POW_LOWER_BOUND_BITS] {
+            assert_eq!(
+                validate_many(&problem, &nonces, *lower_bound_bits),
+                expected(*lower_bound_bits)
+            );
+        }
+        assert!(validate_many(&problem, &[], POW_LOWER_BOUND_BITS).is_empty());
+    }
+
+    #[test]
+    fn test_meets_share_target() {
+        let block_hash = H256::from_low_u64_be(1);
+        let problem = ProofOfWorkProblem::new(block_hash, U256::from(1000));
+        let share_boundary = difficulty_to_boundary(&U256::from(10));
+        let share_nonce =
+            min_satisfying_nonce(&block_hash, &share_boundary).unwrap();
+        let block_nonce =
+            min_satisfying_nonce(&block_hash, &problem.boundary).unwrap();
+
+        let meets_share_target = |nonce: &U256, share_boundary: &U256| {
+            problem.meets_share_target(
+                nonce,
+                share_boundary,
+                POW_LOWER_BOUND_BITS,
+            )
+        };
+        for nonce in &[share_nonce, block_nonce] {
+            let hash = compute(nonce, &block_hash);
+            assert_eq!(
+                meets_share_target(nonce, &share_boundary),
+                (true, pow_hash_to_quality(&hash, nonce))
+            );
+        }
+        let (_, quality) = meets_share_target(&block_nonce, &share_boundary);
+        assert!(quality >= problem.difficulty);
+        let (_, quality) = meets_share_target(&share_nonce, &share_boundary);
+        assert!(quality >= U256::from(10));
+
+        let (meets, _) = meets_share_target(&block_nonce, &U256::zero());
+        assert!(!meets);
+
+        // The hash and the quality are against the given lower bound bits.
+        let hash = compute(&share_nonce, &block_hash);
+        assert_eq!(
+            problem.meets_share_target(&share_nonce, &share_boundary, 63),
+            (
+                ProofOfWorkProblem::validate_hash_against_boundary_with(
+                    &hash,
+                    &share_nonce,
+                    &share_boundary,
+                    63
+                ),
+                pow_hash_to_quality_with(&hash, &share_nonce, 63)
+            )
+        );
+    }
+
+    #[test]
+    fn test_pow_verifier() {
+        let block_hash = H256::from_low_u64_be(1);
+        let problem = ProofOfWorkProblem::new(block_hash, U256::from(1000));
+        let nonce =
+            min_satisfying_nonce(&block_hash, &problem.boundary).unwrap();
+        let valid = ProofOfWorkSolution { nonce };
+        let invalid = ProofOfWorkSolution {
+            nonce: nonce - U256::one(),
+        };
+
+        let verifiers: Vec<Box<dyn PowVerifier>> =
+            vec![Box::new(RealPowVerifier), Box::new(MockPowVerifier)];
+        assert!(verifiers[0].verify(&problem, &valid));
+        assert!(!verifiers[0].verify(&problem, &invalid));
+        assert!(verifiers[1].verify(&problem, &valid));
+        assert!(verifiers[1].verify(&problem, &invalid));
+    }
+
+    fn new_pow_config(
+        damping_numerator: u64, damping_denominator: u64,
+    ) -> ProofOfWorkConfig {
+        let mut pow_config = ProofOfWorkConfig::new(
+            false,
+            false,
+            None,
+            String::new(),
+            0,
+            None,
+            None,
+        );
+        pow_config.damping_numerator = damping_numerator;
+        pow_config.damping_denominator = damping_denominator;
+        pow_config
+    }
+
+    #[test]
+    fn test_target_difficulty_damping() {
+        let undamped = new_pow_config(1, 1);
+        let damped = new_pow_config(1, 2);
+        // 1001 blocks in 100 seconds at difficulty 1e7 is 5 times too fast
+        // at the target of 2 blocks per second.
+        let cur = U256::from(10_000_000);
+        assert_eq!(
+            undamped.target_difficulty(1001, 100, &cur),
+            U256::from(50_000_000)
+        );
+        assert_eq!(
+            damped.target_difficulty(1001, 100, &cur),
+            U256::from(30_000_000)
+        );
+        assert_eq!(
+            new_pow_config(1, 0).target_difficulty(1001, 100, &cur),
+            U256::from(50_000_000)
+        );
+        // 5 times too slow.
+        let cur = U256::from(100_000_000);
+        assert_eq!(
+            undamped.target_difficulty(1001, 10_000, &cur),
+            U256::from(5_000_000)
+        );
+        assert_eq!(
+            damped.target_difficulty(1001, 10_000, &cur),
+            U256::from(52_500_000)
+        );
+        // Overshooting damping can't go below 1.
+        assert_eq!(
+            new_pow_config(3, 1).target_difficulty(1001, 10_000, &cur),
+            U256::one()
+        );
+    }
+
+    #[test]
+    fn test_damped_difficulty_trajectory() {
+        // Mine 1001 blocks per period with a constant hashrate, whose ideal
+        // difficulty at 2 blocks per second is 5e8.
+        let hashrate = 1_000_000_000u64;
+        let ideal = U256::from(hashrate / 2);
+        let next_difficulty = |pow_config: &ProofOfWorkConfig, cur: U256| {
+            let timespan = 1000 * cur.low_u64() / hashrate;
+            let target = pow_config.target_difficulty(1001, timespan, &cur);
+            let (lower, upper) = pow_config.get_adjustment_bound(cur);
+            target.max(lower).min(upper)
+        };
+        let trajectory = |pow_config: &ProofOfWorkConfig| {
+            let mut difficulties = vec![U256::from(100_000_000)];
+            for _ in 0..10 {
+                let cur = *difficulties.last().unwrap();
+                difficulties.push(next_difficulty(pow_config, cur));
+            }
+            difficulties
+        };
+        let undamped = trajectory(&new_pow_config(1, 1));
+        let damped = trajectory(&new_pow_config(1, 2));
+
+        // Both approach the ideal difficulty from below, the damped one more
+        // slowly. The timespan is rounded down to seconds, so the trajectories
+        // may settle slightly off the ideal difficulty.
+        let close_to_ideal = |difficulty: &U256| {
+            let diff = if *difficulty > ideal {
+                *difficulty - ideal
+            } else {
+                ideal - *difficulty
+            };
+            diff < ideal / 100
+        };
+        for i in 1..undamped.len() {
+            assert!(undamped[i] >= undamped[i - 1]);
+            assert!(damped[i] >= damped[i - 1]);
+            assert!(damped[i] <= undamped[i]);
+        }
+        assert!(!close_to_ideal(&damped[4]) && close_to_ideal(&undamped[4]));
+        assert!(close_to_ideal(undamped.last().unwrap()));
+        assert!(close_to_ideal(damped.last().unwrap()));
+    }
+
+    #[test]
+    fn test_target_difficulty_manager_entries() {
+        let manager = TargetDifficultyManager::new(
+            TARGET_DIFFICULTY_CACHE_DEFAULT_CAPACITY,
+        );
+        assert!(manager.entries().is_empty());
+        manager.set(H256::from_low_u64_be(2), U256::from(200));
+        manager.set(H256::from_low_u64_be(1), U256::from(100));
+        manager.set(H256::from_low_u64_be(2), U256::from(300));
+        assert_eq!(
+            manager.entries(),
+            vec![
+                (H256::from_low_u64_be(1), U256::from(100)),
+                (H256::from_low_u64_be(2), U256::from(300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_target_difficulty_manager_eviction() {
+        let manager = TargetDifficultyManager::new(2);
+        manager.set(H256::from_low_u64_be(1), U256::from(100));
+        manager.set(H256::from_low_u64_be(2), U256::from(200));
+        // A get counts as a use, so 2 is now the least recently used.
+        assert_eq!(manager.get(&H256::from_low_u64_be(1)), Some(100.into()));
+        manager.set(H256::from_low_u64_be(3), U256::from(300));
+        assert_eq!(manager.get(&H256::from_low_u64_be(2)), None);
+        assert_eq!(
+            manager.entries(),
+            vec![
+                (H256::from_low_u64_be(1), U256::from(100)),
+                (H256::from_low_u64_be(3), U256::from(300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expected_seconds_to_block() {
+        let difficulty = U256::from(1_000_000);
+        assert_eq!(
+            expected_seconds_to_block(&difficulty, &U256::zero()),
+            std::f64::INFINITY
+        );
+        assert_eq!(
+            expected_seconds_to_block(&difficulty, &U256::from(1000)),
+            1000.0
+        );
+        assert_eq!(
+            expected_seconds_to_block(&(U256::one() << 200), &U256::one()),
+            2f64.powi(200)
+        );
+    }
+
+    #[test]
+    fn test_preview_boundary_change() {
+        let n = U256::from(1000);
+        let boundary = difficulty_to_boundary(&n);
+        assert_eq!(
+            preview_boundary_change(&U256::one(), &n),
+            (ProofOfWorkProblem::NO_BOUNDARY, boundary)
+        );
+        assert_eq!(
+            preview_boundary_change(&n, &U256::one()),
+            (boundary, ProofOfWorkProblem::NO_BOUNDARY)
+        );
+        assert_eq!(
+            preview_boundary_change(&U256::one(), &U256::one()),
+            (ProofOfWorkProblem::NO_BOUNDARY, ProofOfWorkProblem::NO_BOUNDARY)
+        );
+        assert!(boundary < ProofOfWorkProblem::NO_BOUNDARY);
+    }
+
+    #[test]
+    fn test_work_ratio() {
+        assert_eq!(work_ratio(&U256::from(300), &U256::from(100)), 3.0);
+        assert_eq!(work_ratio(&U256::from(100), &U256::from(400)), 0.25);
+        assert_eq!(work_ratio(&U256::MAX, &U256::MAX), 1.0);
+        assert_eq!(
+            work_ratio(&(U256::one() << 255), &(U256::one() << 64)),
+            2f64.powi(191)
+        );
+        assert_eq!(work_ratio(&U256::zero(), &U256::from(5)), 0.0);
+        assert_eq!(
+            work_ratio(&U256::from(5), &U256::zero()),
+            std::f64::INFINITY
+        );
+        assert_eq!(work_ratio(&U256::zero(), &U256::zero()), 1.0);
+    }
+
+    // Append a block to the pivot chain as the only block of its epoch, and
+    // return its hash.
+    fn insert_pivot_block(
+        data_man: &BlockDataManager, parent_hash: H256, height: u64,
+        difficulty: u64, timestamp: u64,
+    ) -> H256
+    {
+        let mut header = BlockHeaderBuilder::new()
+            .with_parent_hash(parent_hash)
+            .with_height(height)
+            .with_difficulty(difficulty.into())
+            .with_timestamp(timestamp)
+            .build();
+        let hash = header.compute_hash();
+        data_man.insert_block_header(hash, Arc::new(header), false);
+        data_man.insert_executed_epoch_set_hashes_to_db(height, &vec![hash]);
+        data_man.insert_skipped_epoch_set_hashes_to_db(height, &vec![]);
+        hash
+    }
+
+    #[test]
+    fn test_cumulative_work_from_headers() {
+        let db_dir = "./test_pow_cumulative_work.db/";
+        {
+            let (data_man, genesis_block) =
+                initialize_data_manager(db_dir, DbType::Rocksdb);
+            let genesis = genesis_block.hash();
+            let mut chain = vec![genesis];
+            for (height, difficulty) in [10u64, 20, 30].iter().enumerate() {
+                let parent_hash = *chain.last().unwrap();
+                chain.push(insert_pivot_block(
+                    &data_man,
+                    parent_hash,
+                    height as u64 + 1,
+                    *difficulty,
+                    0,
+                ));
+            }
+            let tip = chain[3];
+
+            assert_eq!(
+                cumulative_work_from_headers(&data_man, &tip, &genesis),
+                Ok(U512::from(60))
+            );
+            assert_eq!(
+                cumulative_work_from_headers(&data_man, &tip, &chain[1]),
+                Ok(U512::from(50))
+            );
+            assert_eq!(
+                cumulative_work_from_headers(&data_man, &tip, &tip),
+                Ok(U512::zero())
+            );
+
+            let unknown = H256::from_low_u64_be(1);
+            assert_eq!(
+                cumulative_work_from_headers(&data_man, &unknown, &genesis),
+                Err(PowError::MissingHeader(unknown))
+            );
+            // An ancestor off the chain walks past genesis.
+            assert_eq!(
+                cumulative_work_from_headers(&data_man, &tip, &unknown),
+                Err(PowError::MissingHeader(
+                    *genesis_block.block_header.parent_hash()
+                ))
+            );
+        }
+        fs::remove_dir_all(db_dir).unwrap();
+    }
+
+    // Open a data manager with a pivot chain of one adjustment period of 2
+    // epochs, and return it with the hash of the period boundary and the
+    // difficulty recomputed for the next period.
+    fn new_audit_chain(
+        db_dir: &str, pow_config: &ProofOfWorkConfig,
+    ) -> (Arc<BlockDataManager>, H256, U256) {
+        let (data_man, genesis_block) =
+            initialize_data_manager(db_dir, DbType::Rocksdb);
+        let first =
+            insert_pivot_block(&data_man, genesis_block.hash(), 1, 1000, 100);
+        let boundary = insert_pivot_block(&data_man, first, 2, 1000, 200);
+        let expected = compute_target_difficulty(
+            &data_man,
+            pow_config,
+            &boundary,
+            |_: &H256| 1,
+        )
+        .unwrap();
+        (data_man, boundary, expected)
+    }
+
+    #[test]
+    fn test_audit_difficulty_adjustments_within_tolerance() {
+        let db_dir = "./test_pow_audit_within_tolerance.db/";
+        {
+            let mut pow_config = new_pow_config(1, 1);
+            pow_config.difficulty_adjustment_epoch_period = 2;
+            let (data_man, boundary, expected) =
+                new_audit_chain(db_dir, &pow_config);
+            // No boundary has its next epoch yet.
+            assert_eq!(
+                audit_difficulty_adjustments(&data_man, &pow_config, 0, 2),
+                Ok(vec![])
+            );
+
+            let recorded = expected + DIFFICULTY_AUDIT_TOLERANCE;
+            insert_pivot_block(&data_man, boundary, 3, recorded.as_u64(), 300);
+            assert_eq!(
+                audit_difficulty_adjustments(&data_man, &pow_config, 0, 2),
+                Ok(vec![])
+            );
+        }
+        fs::remove_dir_all(db_dir).unwrap();
+    }
+
+    #[test]
+    fn test_audit_difficulty_adjustments_outside_tolerance() {
+        let db_dir = "./test_pow_audit_outside_tolerance.db/";
+        {
+            let mut pow_config = new_pow_config(1, 1);
+            pow_config.difficulty_adjustment_epoch_period = 2;
+            let (data_man, boundary, expected) =
+                new_audit_chain(db_dir, &pow_config);
+
+            let recorded = expected + DIFFICULTY_AUDIT_TOLERANCE + 1;
+            insert_pivot_block(&data_man, boundary, 3, recorded.as_u64(), 300);
+            assert_eq!(
+                audit_difficulty_adjustments(&data_man, &pow_config, 0, 2),
+                Ok(vec![DifficultyAuditEntry {
+                    boundary_height: 2,
+                    boundary_hash: boundary,
+                    expected,
+                    recorded,
+                }])
+            );
+            // The boundary is out of the audited heights.
+            assert_eq!(
+                audit_difficulty_adjustments(&data_man, &pow_config, 3, 3),
+                Ok(vec![])
+            );
+        }
+        fs::remove_dir_all(db_dir).unwrap();
+    }
 }
@@ -20,6 +20,7 @@ use std::{collections::HashMap, sync::Arc};
 /* Signal and Slots begin */
 use primitives::{
     SlotTxQueue, SlotTx, SignalLocation, SlotLocation, SignalInfo, SlotInfo,
+    BindError,
 };
 /* Signal and Slots end */
 //////////////////////////////////////////////////////////////////////
@@ -1217,11 +1218,14 @@ impl OverlayAccount {
         self.slot_tx_queue.as_ref().unwrap().is_empty()
     }
 
-    // Add a slot to the slot list.
-    pub fn add_to_slot_list(&mut self, db: &StateDb, sig_loc: &SignalLocation, slot_info: &SlotInfo) {
+    // Add a slot to the slot list. The signal is left unchanged if its slot list is full.
+    pub fn add_to_slot_list(
+        &mut self, db: &StateDb, sig_loc: &SignalLocation, slot_info: &SlotInfo
+    ) -> Result<(), BindError> {
         let mut sig_info = self.signal_at(db, sig_loc).unwrap().clone();
-        sig_info.add_to_slot_list(slot_info);
+        sig_info.add_to_slot_list(slot_info)?;
         self.set_signal(sig_info);
+        Ok(())
     }
 
     // Add a signal to the bind list.
@@ -11,7 +11,10 @@ use crate::{
     executive::SPONSOR_WHITELIST_CONTROL_CONTRACT_ADDRESS,
     hash::KECCAK_EMPTY,
     parameters::staking::*,
-    statedb::{ErrorKind as DbErrorKind, Result as DbResult, StateDb},
+    statedb::{
+        ErrorKind as DbErrorKind, GlobalStatistics, Result as DbResult,
+        StateDb,
+    },
     storage::StateRootWithAuxInfo,
     transaction_pool::SharedTransactionPool,
     vm_factory::VmFactory,
@@ -42,8 +45,9 @@ mod signal_tests;
 
 use primitives::{
     SlotTxQueue, SlotTx, SignalInfo, SlotInfo, SignalLocation, SlotLocation,
-    SlotTxAddressList,
+    SlotTxAddressList, BindError,
 };
+use crate::parameters::signal::ALLOW_SUB_BASE_SLOT_GAS_RATIO;
 /* Signal and Slots end */
 //////////////////////////////////////////////////////////////////////
 
@@ -131,16 +135,7 @@ impl State {
     pub fn new(
         db: StateDb, vm: VmFactory, spec: &Spec, block_number: u64,
     ) -> Self {
-        let annual_interest_rate =
-            db.get_annual_interest_rate().expect("no db error");
-        let accumulate_interest_rate =
-            db.get_accumulate_interest_rate().expect("no db error");
-        let total_issued_tokens =
-            db.get_total_issued_tokens().expect("No db error");
-        let total_staking_tokens =
-            db.get_total_staking_tokens().expect("No db error");
-        let total_storage_tokens =
-            db.get_total_storage_tokens().expect("No db error");
+        let statistics = db.get_global_statistics().expect("No db error");
         /*
         let account_start_nonce = (block_number
             * ESTIMATED_MAX_BLOCK_SIZE_IN_TRANSACTION_COUNT as u64)
@@ -160,12 +155,12 @@ impl State {
             account_start_nonce,
             contract_start_nonce,
             staking_state: StakingState {
-                total_issued_tokens,
-                total_staking_tokens,
-                total_storage_tokens,
-                interest_rate_per_block: annual_interest_rate
+                total_issued_tokens: statistics.total_issued,
+                total_staking_tokens: statistics.total_staking,
+                total_storage_tokens: statistics.total_storage,
+                interest_rate_per_block: statistics.interest_rate
                     / U256::from(BLOCKS_PER_YEAR),
-                accumulate_interest_rate,
+                accumulate_interest_rate: statistics.accumulate_interest_rate,
             },
             block_number,
             vm,
@@ -1013,30 +1008,21 @@ impl State {
     }
 
     fn commit_staking_state(
-        &mut self, mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+        &mut self, debug_record: Option<&mut ComputeEpochDebugRecord>,
     ) -> DbResult<()> {
-        self.db.set_annual_interest_rate(
-            &(self.staking_state.interest_rate_per_block
-                * U256::from(BLOCKS_PER_YEAR)),
-            debug_record.as_deref_mut(),
-        )?;
-        self.db.set_accumulate_interest_rate(
-            &self.staking_state.accumulate_interest_rate,
-            debug_record.as_deref_mut(),
-        )?;
-        self.db.set_total_issued_tokens(
-            &self.staking_state.total_issued_tokens,
-            debug_record.as_deref_mut(),
-        )?;
-        self.db.set_total_staking_tokens(
-            &self.staking_state.total_staking_tokens,
-            debug_record.as_deref_mut(),
-        )?;
-        self.db.set_total_storage_tokens(
-            &self.staking_state.total_storage_tokens,
+        self.db.set_global_statistics(
+            &GlobalStatistics {
+                total_issued: self.staking_state.total_issued_tokens,
+                total_staking: self.staking_state.total_staking_tokens,
+                total_storage: self.staking_state.total_storage_tokens,
+                interest_rate: self.staking_state.interest_rate_per_block
+                    * U256::from(BLOCKS_PER_YEAR),
+                accumulate_interest_rate: self
+                    .staking_state
+                    .accumulate_interest_rate,
+            },
             debug_record,
-        )?;
-        Ok(())
+        )
     }
 
     /// Assume that only contract with zero `collateral_for_storage` will be
@@ -1545,17 +1531,15 @@ impl State {
         assert!(self.checkpoints.get_mut().is_empty());
         assert!(self.staking_state_checkpoints.get_mut().is_empty());
         self.cache.get_mut().clear();
+        let statistics =
+            self.db.get_global_statistics().expect("No db error");
         self.staking_state.interest_rate_per_block =
-            self.db.get_annual_interest_rate().expect("no db error")
-                / U256::from(BLOCKS_PER_YEAR);
+            statistics.interest_rate / U256::from(BLOCKS_PER_YEAR);
         self.staking_state.accumulate_interest_rate =
-            self.db.get_accumulate_interest_rate().expect("no db error");
-        self.staking_state.total_issued_tokens =
-            self.db.get_total_issued_tokens().expect("No db error");
-        self.staking_state.total_staking_tokens =
-            self.db.get_total_staking_tokens().expect("No db error");
-        self.staking_state.total_storage_tokens =
-            self.db.get_total_storage_tokens().expect("No db error");
+            statistics.accumulate_interest_rate;
+        self.staking_state.total_issued_tokens = statistics.total_issued;
+        self.staking_state.total_staking_tokens = statistics.total_staking;
+        self.staking_state.total_storage_tokens = statistics.total_storage;
 
         //////////////////////////////////////////////////////////////////////
         /* Signal and Slots begin */
@@ -1623,6 +1607,11 @@ impl State {
             gas_limit, 
             gas_ratio,
         );
+        if !ALLOW_SUB_BASE_SLOT_GAS_RATIO
+            && slot_info.validate_min_ratio().is_err()
+        {
+            return Ok(false);
+        }
         self.require_exists(slot_address, false)?
             .set_slot(slot_info);
         Ok(true)
@@ -1708,6 +1697,8 @@ impl State {
     // Incomplete database error is returned if either the signal or slot cannot
     // be retrieved from the database.
     // Error is also returned if signal and slots don't match in argument count.
+    // Signal slot list full error is returned if the signal already has the maximum number of
+    // bound slots.
     // TODO: Figure out if we should throw an error if argument counts between the signal
     // and slots don't match or not. If they don't should an error be thrown?
     pub fn bind_slot_to_signal(
@@ -1746,9 +1737,12 @@ impl State {
         //     .into());
         // }
 
-        // Signal account.
-        self.require_exists(sig_loc.address(), false)?
+        // Signal account. Nothing is bound if the slot list of the signal is full.
+        let bind_result = self.require_exists(sig_loc.address(), false)?
             .add_to_slot_list(&self.db, sig_loc, &slot_info);
+        if let Err(BindError::SlotListFull { limit }) = bind_result {
+            bail!(DbErrorKind::SignalSlotListFull(*sig_loc.address(), limit));
+        }
         // Slot account.
         self.require_exists(&slot_loc.address(), false)?
             .add_to_bind_list(&self.db, slot_loc, sig_loc);
@@ -1807,17 +1801,17 @@ impl State {
                 .into());
             }
         };
-        // Create and queue slot transactions. If the delay is not 0, then the slot
+        // Create and queue slot transactions in the canonical slot order, so that all the nodes
+        // create them in the same order. If the delay is not 0, then the slot
         // transaction is queued on the global queue. If it is 0, we queue it directly
         // to the individual account queues + add address to the ready slot tx address list.
         let target_epoch_height = current_epoch_height + signal_delay;
         if signal_delay == 0 {
-            for slot in sig_info.slot_list() {
-                let tx = SlotTx::new(
-                    slot, 
-                    &target_epoch_height, 
-                    raw_data,
-                );
+            for slot in sig_info.sorted_slots() {
+                let tx = SlotTx::builder(slot)
+                    .epoch_height(target_epoch_height)
+                    .raw_data(raw_data)
+                    .build();
                 let contract_address = tx.address().clone();
                 self.ensure_cached(&contract_address, RequireCache::SlotTxQueue, |_acc| {})?;
                 self.require_exists(&contract_address, false)?
@@ -1826,12 +1820,11 @@ impl State {
             }
         }
         else {
-            for slot in sig_info.slot_list() {
-                let tx = SlotTx::new(
-                    slot, 
-                    &target_epoch_height, 
-                    raw_data,
-                );
+            for slot in sig_info.sorted_slots() {
+                let tx = SlotTx::builder(slot)
+                    .epoch_height(target_epoch_height)
+                    .raw_data(raw_data)
+                    .build();
                 self.enqueue_slot_tx_to_global_queue(tx)?;
             }
         }
@@ -0,0 +1,189 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use super::{Result, StateDb};
+use crate::{
+    consensus::debug::ComputeEpochDebugRecord, storage::StateRootWithAuxInfo,
+};
+use cfx_types::{Address, H256};
+use lru::LruCache;
+use parking_lot::Mutex;
+use primitives::{Account, CodeInfo, EpochId, StorageKey, StorageRoot};
+use std::hash::Hash;
+
+/// Cache hit and miss counts of a `CachedStateDb`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Caches {
+    accounts: LruCache<Address, Option<Account>>,
+    code: LruCache<(Address, H256), Option<CodeInfo>>,
+    storage_roots: LruCache<Address, Option<StorageRoot>>,
+    stats: CacheStats,
+}
+
+impl Caches {
+    fn lookup<K, V, F>(
+        cache: &mut LruCache<K, Option<V>>, stats: &mut CacheStats, key: K,
+        read: F,
+    ) -> Result<Option<V>>
+    where
+        K: Eq + Hash,
+        V: Clone,
+        F: FnOnce() -> Result<Option<V>>,
+    {
+        if let Some(value) = cache.get(&key) {
+            stats.hits += 1;
+            return Ok(value.clone());
+        }
+        stats.misses += 1;
+        let value = read()?;
+        cache.put(key, value.clone());
+        Ok(value)
+    }
+
+    fn invalidate(&mut self, address: &Address) {
+        self.accounts.pop(address);
+        self.storage_roots.pop(address);
+        let code_keys: Vec<_> = self
+            .code
+            .iter()
+            .map(|(key, _)| *key)
+            .filter(|(code_address, _)| code_address == address)
+            .collect();
+        for key in &code_keys {
+            self.code.pop(key);
+        }
+    }
+}
+
+/// A `StateDb` wrapper which memoizes the decoded accounts, code and storage
+/// roots it reads, for code reading the same accounts repeatedly. Each kind
+/// of value is kept in an LRU cache of at most `max_entries` entries, with
+/// missing values cached too.
+///
+/// Writes must go through the wrapper: any write under an address drops the
+/// cached values of the address. Storage roots are also dropped when the
+/// state root is computed, since that changes the merkle hashes.
+pub struct CachedStateDb {
+    db: StateDb,
+    caches: Mutex<Caches>,
+}
+
+impl CachedStateDb {
+    /// `max_entries` is the capacity of each cache and must not be zero.
+    pub fn new(db: StateDb, max_entries: usize) -> Self {
+        assert!(max_entries > 0, "CachedStateDb needs a nonzero capacity");
+        CachedStateDb {
+            db,
+            caches: Mutex::new(Caches {
+                accounts: LruCache::new(max_entries),
+                code: LruCache::new(max_entries),
+                storage_roots: LruCache::new(max_entries),
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    pub fn into_inner(self) -> StateDb { self.db }
+
+    pub fn state_db(&self) -> &StateDb { &self.db }
+
+    pub fn stats(&self) -> CacheStats { self.caches.lock().stats }
+
+    pub fn get_account(&self, address: &Address) -> Result<Option<Account>> {
+        let caches = &mut *self.caches.lock();
+        Caches::lookup(
+            &mut caches.accounts,
+            &mut caches.stats,
+            *address,
+            || self.db.get_account(address),
+        )
+    }
+
+    pub fn get_code(
+        &self, address: &Address, code_hash: &H256,
+    ) -> Result<Option<CodeInfo>> {
+        let caches = &mut *self.caches.lock();
+        Caches::lookup(
+            &mut caches.code,
+            &mut caches.stats,
+            (*address, *code_hash),
+            || self.db.get_code(address, code_hash),
+        )
+    }
+
+    pub fn get_storage_root(
+        &self, address: &Address,
+    ) -> Result<Option<StorageRoot>> {
+        let caches = &mut *self.caches.lock();
+        Caches::lookup(
+            &mut caches.storage_roots,
+            &mut caches.stats,
+            *address,
+            || self.db.get_storage_root(address),
+        )
+    }
+
+    pub fn set<T>(
+        &mut self, key: StorageKey, value: &T,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    where
+        T: ::rlp::Encodable,
+    {
+        self.invalidate(&key);
+        self.db.set(key, value, debug_record)
+    }
+
+    pub fn set_raw(
+        &mut self, key: StorageKey, value: Box<[u8]>,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    {
+        self.invalidate(&key);
+        self.db.set_raw(key, value, debug_record)
+    }
+
+    pub fn delete(
+        &mut self, key: StorageKey,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    {
+        self.invalidate(&key);
+        self.db.delete(key, debug_record)
+    }
+
+    pub fn delete_all(
+        &mut self, key_prefix: StorageKey,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>>
+    {
+        self.invalidate(&key_prefix);
+        self.db.delete_all(key_prefix, debug_record)
+    }
+
+    pub fn compute_state_root(&mut self) -> Result<StateRootWithAuxInfo> {
+        self.caches.get_mut().storage_roots.clear();
+        self.db.compute_state_root()
+    }
+
+    pub fn commit(
+        &mut self, epoch_id: EpochId,
+    ) -> Result<StateRootWithAuxInfo> {
+        self.caches.get_mut().storage_roots.clear();
+        self.db.commit(epoch_id)
+    }
+
+    // Every storage key starts with the address it belongs to.
+    fn invalidate(&mut self, key: &StorageKey) {
+        let key_bytes = key.to_key_bytes();
+        let address =
+            Address::from_slice(&key_bytes[..StorageKey::ACCOUNT_BYTES]);
+        self.caches.get_mut().invalidate(&address);
+    }
+}
@@ -3,7 +3,7 @@
 // See http://www.gnu.org/licenses/
 
 use crate::storage::Error as StorageError;
-use cfx_types::Address;
+use cfx_types::{Address, H256, U256};
 use rlp::DecoderError;
 
 error_chain! {
@@ -20,5 +20,57 @@ error_chain! {
             description("incomplete database")
             display("incomplete database: address={:?}", address)
         }
+
+        InsufficientBalance(address: Address, required: U256, got: U256) {
+            description("insufficient balance")
+            display(
+                "insufficient balance: address={:?}, required={}, got={}",
+                address, required, got
+            )
+        }
+
+        BalanceOverflow(address: Address) {
+            description("balance overflow")
+            display("balance overflow: address={:?}", address)
+        }
+
+        SnapshotOfDirtyState {
+            description("snapshot of a state with uncommitted changes")
+            display("snapshot of a state with uncommitted changes")
+        }
+
+        DecodeError(key: Vec<u8>, error: DecoderError) {
+            description("decode error")
+            display("decode error: key={:?}, error={}", key, error)
+        }
+
+        DecodeManyError(index: usize, key: Vec<u8>, error: DecoderError) {
+            description("decode error in batched read")
+            display(
+                "decode error in batched read: index={}, key={:?}, error={}",
+                index, key, error
+            )
+        }
+
+        SignalSlotListFull(address: Address, limit: usize) {
+            description("signal slot list full")
+            display(
+                "signal slot list full: address={:?}, limit={}",
+                address, limit
+            )
+        }
+
+        UnknownCheckpoint(id: usize) {
+            description("unknown checkpoint")
+            display("unknown checkpoint: id={}", id)
+        }
+
+        CodeHashMismatch(address: Address, expected: H256, got: H256) {
+            description("code hash mismatch")
+            display(
+                "code hash mismatch: address={:?}, expected={:?}, got={:?}",
+                address, expected, got
+            )
+        }
     }
 }
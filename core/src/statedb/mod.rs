@@ -4,22 +4,30 @@
 
 use crate::{
     executive::STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
+    hash::{keccak, KECCAK_EMPTY},
     parameters::staking::*,
     storage::{
         Error as StorageError, ErrorKind as StorageErrorKind, StateProof,
         StateRootWithAuxInfo, StorageState, StorageStateTrait,
     },
 };
-use cfx_types::{Address, H256, U256};
+use cfx_types::{Address, H256, U256, U512};
 use primitives::{
     Account, CodeInfo, DepositList, EpochId, StorageKey, StorageLayout,
     StorageRoot, VoteStakeList, MERKLE_NULL_NODE,
 };
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    convert::TryFrom,
+    mem,
+};
 
 //////////////////////////////////////////////////////////////////////
 /* Signal and Slots begin */
 use primitives::{
-    SlotTxQueue, SignalInfo, SlotInfo, SlotTxAddressList
+    SlotTx, SlotTxQueue, SignalInfo, SignalLocation, SlotInfo, SlotLocation,
+    SlotTxAddressList,
 };
 use crate::signal::{
     GLOBAL_SLOT_TX_QUEUE_ADDRESS, GLOBAL_SLOT_TX_ACCOUNT_LIST_ADDRESS
@@ -27,13 +35,152 @@ use crate::signal::{
 /* Signal and Slots end */
 //////////////////////////////////////////////////////////////////////
 
+mod cached_state_db;
 mod error;
+mod portable_account;
 
-pub use self::error::{Error, ErrorKind, Result};
+pub use self::{
+    cached_state_db::{CacheStats, CachedStateDb},
+    error::{Error, ErrorKind, Result},
+    portable_account::PortableAccount,
+};
 use crate::consensus::debug::{ComputeEpochDebugRecord, StateOp};
 
+//////////////////////////////////////////////////////////////////////
+/* Signal and Slots begin */
+
+// Summary of the dangling signal and slot state removed by
+// StateDb::gc_signal_slot_state.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GcReport {
+    // Bindings removed from signal slot lists because the SlotInfo is gone.
+    pub removed_slot_bindings: Vec<(SignalLocation, SlotLocation)>,
+    // Bindings removed from slot bind lists because the SignalInfo is gone.
+    pub removed_signal_bindings: Vec<(SlotLocation, SignalLocation)>,
+    // SlotInfos deleted because all the signals they were bound to are gone.
+    pub deleted_slots: Vec<SlotLocation>,
+}
+
+impl GcReport {
+    pub fn is_empty(&self) -> bool {
+        self.removed_slot_bindings.is_empty()
+            && self.removed_signal_bindings.is_empty()
+            && self.deleted_slots.is_empty()
+    }
+}
+/* Signal and Slots end */
+//////////////////////////////////////////////////////////////////////
+
+/// A cheap summary of the changes written to a `StateDb` since the last
+/// commit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EpochChangeSummary {
+    /// Number of distinct addresses with a key set or deleted.
+    pub accounts_touched: usize,
+    /// Total size of the values set, in bytes.
+    pub bytes_written: u64,
+    /// Number of slot transactions added to the global slot transaction
+    /// queues.
+    pub slot_txs_enqueued: u64,
+}
+
+/// Counters of the write operations made on a `StateDb`, collected once
+/// `StateDb::enable_metrics` is called. Unlike `EpochChangeSummary`, they
+/// are not reset on commit.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StateDbMetrics {
+    /// Number of `set_raw` calls.
+    pub writes: u64,
+    /// Number of `delete` and `delete_all` calls.
+    pub deletes: u64,
+    /// Total size of the values set, in bytes.
+    pub bytes_written: u64,
+}
+
+/// The global staking values kept in the storage of
+/// `STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS`, read and written together by
+/// `get_global_statistics` and `set_global_statistics`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlobalStatistics {
+    pub total_issued: U256,
+    pub total_staking: U256,
+    pub total_storage: U256,
+    /// The annual interest rate.
+    pub interest_rate: U256,
+    pub accumulate_interest_rate: U256,
+}
+
+/// A disagreement between the recorded total staking tokens and the sum of
+/// the deposits, found by `StateDb::verify_total_staking_tokens`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StakingDrift {
+    /// The value of `get_total_staking_tokens`.
+    pub recorded: U256,
+    /// The sum of the amounts in all deposit lists.
+    pub deposited: U512,
+}
+
+/// A storage key whose raw value differs between two states, found by
+/// `StateDb::diff`. A missing side means the key is absent there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageDiffEntry {
+    pub key: Vec<u8>,
+    pub left: Option<Box<[u8]>>,
+    pub right: Option<Box<[u8]>>,
+}
+
+/// Identifies a checkpoint taken by `StateDb::checkpoint`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckpointId(usize);
+
 pub struct StateDb {
     storage: StorageState,
+    // Change counters for the current epoch, reset on commit.
+    touched_addresses: HashSet<Vec<u8>>,
+    bytes_written: u64,
+    slot_txs_enqueued: u64,
+    // Memoized results of get_storage_root, dropped when the storage of the
+    // address is written.
+    storage_root_cache: Mutex<HashMap<Address, Option<StorageRoot>>>,
+    // A stack of undo logs, one per checkpoint, holding the previous values
+    // of the keys written after the checkpoint.
+    checkpoints: Vec<Vec<(Vec<u8>, Option<Box<[u8]>>)>>,
+    metrics: Option<StateDbMetrics>,
+    // The keys written since the last commit, tracked only after
+    // enable_changeset_tracking.
+    changed_keys: Option<BTreeSet<Vec<u8>>>,
+}
+
+/// A read-only view of a `StateDb`, opened by `StateDb::readonly_snapshot`.
+/// It doesn't observe writes made to the `StateDb` afterwards.
+///
+/// The view keeps the underlying storage of the state it was opened on
+/// alive, so it should be dropped once the reads are done rather than kept
+/// around across epochs.
+pub struct StateDbSnapshot {
+    storage: StorageState,
+}
+
+impl StateDbSnapshot {
+    pub fn get<T>(&self, key: StorageKey) -> Result<Option<T>>
+    where T: ::rlp::Decodable {
+        let raw = match self.storage.get(key)? {
+            None => return Ok(None),
+            Some(raw) => raw,
+        };
+        match ::rlp::decode::<T>(raw.as_ref()) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => Err(ErrorKind::DecodeError(key.to_key_bytes(), e).into()),
+        }
+    }
+
+    pub fn get_raw(&self, key: StorageKey) -> Result<Option<Box<[u8]>>> {
+        Ok(self.storage.get(key)?)
+    }
+
+    pub fn get_account(&self, address: &Address) -> Result<Option<Account>> {
+        self.get::<Account>(StorageKey::new_account_key(address))
+    }
 }
 
 impl StateDb {
@@ -50,10 +197,56 @@ impl StateDb {
     /* Signal and Slots end */
     //////////////////////////////////////////////////////////////////////
     
-    pub fn new(storage: StorageState) -> Self { StateDb { storage } }
+    pub fn new(storage: StorageState) -> Self {
+        StateDb {
+            storage,
+            touched_addresses: HashSet::new(),
+            bytes_written: 0,
+            slot_txs_enqueued: 0,
+            storage_root_cache: Default::default(),
+            checkpoints: Vec::new(),
+            metrics: None,
+            changed_keys: None,
+        }
+    }
+
+    /// Start collecting `StateDbMetrics`. Does nothing if they are already
+    /// collected.
+    pub fn enable_metrics(&mut self) {
+        if self.metrics.is_none() {
+            self.metrics = Some(StateDbMetrics::default());
+        }
+    }
+
+    /// The metrics collected since `enable_metrics`, or `None` if it wasn't
+    /// called.
+    pub fn metrics(&self) -> Option<StateDbMetrics> { self.metrics }
+
+    /// Start tracking the keys written, for `commit_with_changeset`. Does
+    /// nothing if they are already tracked.
+    pub fn enable_changeset_tracking(&mut self) {
+        if self.changed_keys.is_none() {
+            self.changed_keys = Some(BTreeSet::new());
+        }
+    }
+
+    /// Open a read-only view of the state as it is now. Only committed trie
+    /// nodes can be shared with the view, so this fails when there are
+    /// writes which haven't been committed yet.
+    pub fn readonly_snapshot(&self) -> Result<StateDbSnapshot> {
+        match self.storage.readonly_snapshot() {
+            Some(storage) => Ok(StateDbSnapshot { storage }),
+            None => Err(ErrorKind::SnapshotOfDirtyState.into()),
+        }
+    }
 
+    /// Writes through the returned storage bypass the memoized storage
+    /// roots, so they are all dropped.
     #[allow(unused)]
-    pub fn get_storage_mut(&mut self) -> &mut StorageState { &mut self.storage }
+    pub fn get_storage_mut(&mut self) -> &mut StorageState {
+        self.storage_root_cache.get_mut().clear();
+        &mut self.storage
+    }
 
     pub fn get<T>(&self, key: StorageKey) -> Result<Option<T>>
     where T: ::rlp::Decodable {
@@ -66,7 +259,37 @@ impl StateDb {
                 return Err(e.into());
             }
         };
-        Ok(Some(::rlp::decode::<T>(raw.as_ref())?))
+        match ::rlp::decode::<T>(raw.as_ref()) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => Err(ErrorKind::DecodeError(key.to_key_bytes(), e).into()),
+        }
+    }
+
+    /// Read and decode several keys, preserving their order, with `None` for
+    /// the missing ones. A value which fails to decode is reported with its
+    /// index and key. The storage has no batched lookup, so the keys are
+    /// still read one by one.
+    pub fn get_many<T>(&self, keys: &[StorageKey]) -> Result<Vec<Option<T>>>
+    where T: ::rlp::Decodable {
+        let mut values = Vec::with_capacity(keys.len());
+        for (index, key) in keys.iter().enumerate() {
+            let value = match self.storage.get(*key)? {
+                None => None,
+                Some(raw) => match ::rlp::decode::<T>(raw.as_ref()) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        return Err(ErrorKind::DecodeManyError(
+                            index,
+                            key.to_key_bytes(),
+                            e,
+                        )
+                        .into());
+                    }
+                },
+            };
+            values.push(value);
+        }
+        Ok(values)
     }
 
     pub fn get_code(
@@ -87,6 +310,85 @@ impl StateDb {
         self.get::<VoteStakeList>(StorageKey::new_vote_list_key(address))
     }
 
+    /// Read both staking lists of an account, each `None` if the account
+    /// doesn't have it. The storage has no batched lookup, so this is the
+    /// same two reads as `get_deposit_list` and `get_vote_list`.
+    pub fn get_staking_lists(
+        &self, address: &Address,
+    ) -> Result<(Option<DepositList>, Option<VoteStakeList>)> {
+        Ok((self.get_deposit_list(address)?, self.get_vote_list(address)?))
+    }
+
+    /// The total tokens an account has locked in staking, i.e. the sum of the
+    /// `amount` of every entry in its deposit list, which equals its
+    /// `staking_balance`. Vote locks don't add to it: a vote locks part of
+    /// the tokens which are already deposited, and `vote_lock` never locks
+    /// more than the staking balance. The sum is computed in U512 and
+    /// saturates at U256::MAX.
+    pub fn total_locked_stake(&self, address: &Address) -> Result<U256> {
+        let total = self
+            .get_deposit_list(address)?
+            .map_or(U512::zero(), |deposit_list| {
+                deposit_list.iter().fold(U512::zero(), |total, deposit| {
+                    total + U512::from(deposit.amount)
+                })
+            });
+        Ok(U256::try_from(total).unwrap_or(U256::max_value()))
+    }
+
+    /// Read the deposit lists of all accounts, in the order of their storage
+    /// keys. A list failing to decode gives an error for that item without
+    /// failing the others. The storage has no lazy cursor over the state, so
+    /// this is a full scan of the state, collected in memory.
+    pub fn iter_deposit_lists(
+        &self,
+    ) -> Result<Vec<Result<(Address, DepositList)>>> {
+        let entries = self.read_all_state()?.unwrap_or_default();
+        Ok(entries
+            .into_iter()
+            .filter_map(|(key, raw)| {
+                match StorageKey::from_key_bytes(&key) {
+                    StorageKey::DepositListKey(address_bytes) => {
+                        let address = Address::from_slice(address_bytes);
+                        Some(
+                            ::rlp::decode::<DepositList>(raw.as_ref())
+                                .map(|deposit_list| (address, deposit_list))
+                                .map_err(Into::into),
+                        )
+                    }
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+
+    /// Read the vote stake lists of all accounts, like `iter_deposit_lists`:
+    /// in the order of their storage keys, i.e. by address, with an error for
+    /// a list failing to decode without failing the others. The vote lists
+    /// don't share a key prefix and the storage has no lazy cursor over the
+    /// state, so this is a full scan of the state, collected in memory.
+    pub fn iter_vote_lists(
+        &self,
+    ) -> Result<Vec<Result<(Address, VoteStakeList)>>> {
+        let entries = self.read_all_state()?.unwrap_or_default();
+        Ok(entries
+            .into_iter()
+            .filter_map(|(key, raw)| {
+                match StorageKey::from_key_bytes(&key) {
+                    StorageKey::VoteListKey(address_bytes) => {
+                        let address = Address::from_slice(address_bytes);
+                        Some(
+                            ::rlp::decode::<VoteStakeList>(raw.as_ref())
+                                .map(|vote_list| (address, vote_list))
+                                .map_err(Into::into),
+                        )
+                    }
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+
     pub fn get_storage_layout(
         &self, address: &Address,
     ) -> Result<Option<StorageLayout>> {
@@ -109,30 +411,467 @@ impl StateDb {
         self.get::<Account>(StorageKey::new_account_key(address))
     }
 
+    /// Whether the account exists, without decoding it. An empty value is
+    /// treated as a missing account.
+    pub fn account_exists(&self, address: &Address) -> Result<bool> {
+        Ok(self
+            .get_raw(StorageKey::new_account_key(address))?
+            .map_or(false, |raw| !raw.is_empty()))
+    }
+
+    /// Read an account like `get_account`, together with the proof of the
+    /// account key.
+    pub fn get_account_with_proof(
+        &self, address: &Address,
+    ) -> Result<(Option<Account>, StateProof)> {
+        let key = StorageKey::new_account_key(address);
+        let (maybe_raw, proof) = self.get_raw_with_proof(key)?;
+        let maybe_account = match maybe_raw {
+            None => None,
+            Some(raw) => match ::rlp::decode::<Account>(raw.as_ref()) {
+                Ok(account) => Some(account),
+                Err(e) => {
+                    return Err(
+                        ErrorKind::DecodeError(key.to_key_bytes(), e).into()
+                    );
+                }
+            },
+        };
+        Ok((maybe_account, proof))
+    }
+
+    /// Whether the account has code, i.e. its code hash isn't `KECCAK_EMPTY`,
+    /// the code hash accounts are created with. Missing accounts aren't
+    /// contracts.
+    pub fn is_contract(&self, address: &Address) -> Result<bool> {
+        Ok(self
+            .get_account(address)?
+            .map_or(false, |account| account.code_hash != KECCAK_EMPTY))
+    }
+
+    /// Check that the code hash of an account matches the hash of the code
+    /// stored under it. Accounts which don't exist or have no stored code
+    /// are consistent.
+    pub fn verify_account_code_hash(&self, address: &Address) -> Result<bool> {
+        Ok(self.find_code_hash_mismatch(address)?.is_none())
+    }
+
+    /// Fix the code hash of an account which disagrees with the hash of its
+    /// stored code, see `verify_account_code_hash`. The code is moved to the
+    /// key of the recomputed hash, so that it can still be found from the
+    /// account. Returns whether a fix was applied.
+    pub fn repair_account_code_hash(
+        &mut self, address: &Address,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<bool>
+    {
+        let (mut account, code, code_hash) =
+            match self.find_code_hash_mismatch(address)? {
+                Some(mismatch) => mismatch,
+                None => return Ok(false),
+            };
+        self.delete(
+            StorageKey::new_code_key(address, &account.code_hash),
+            debug_record.as_deref_mut(),
+        )?;
+        self.set::<CodeInfo>(
+            StorageKey::new_code_key(address, &code_hash),
+            &code,
+            debug_record.as_deref_mut(),
+        )?;
+        account.code_hash = code_hash;
+        self.set::<Account>(
+            StorageKey::new_account_key(address),
+            &account,
+            debug_record,
+        )?;
+        Ok(true)
+    }
+
+    // Returns the account, its stored code and the recomputed code hash if
+    // the code hash of the account is wrong.
+    fn find_code_hash_mismatch(
+        &self, address: &Address,
+    ) -> Result<Option<(Account, CodeInfo, H256)>> {
+        let account = match self.get_account(address)? {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        if account.code_hash == KECCAK_EMPTY {
+            return Ok(None);
+        }
+        let code = match self.get_code(address, &account.code_hash)? {
+            Some(code) => code,
+            None => return Ok(None),
+        };
+        let code_hash = keccak(&code.code);
+        if code_hash == account.code_hash {
+            Ok(None)
+        } else {
+            Ok(Some((account, code, code_hash)))
+        }
+    }
+
+    /// Read the balance and the storage collateral of an account with a
+    /// single decode, for admission checks which must account for the funds
+    /// locked as collateral.
+    pub fn get_account_balance_and_collateral(
+        &self, address: &Address,
+    ) -> Result<Option<(U256, U256)>> {
+        Ok(self
+            .get_account(address)?
+            .map(|account| (account.balance, account.collateral_for_storage)))
+    }
+
+    /// Move `amount` from the balance of `from` to the balance of `to`. Both
+    /// sides are checked before anything is written, and the debit of `from`
+    /// is written back if the credit of `to` fails, so on error nothing is
+    /// applied. A missing `to` account is created with zero nonce. A
+    /// transfer of zero, or from an account to itself, only validates the
+    /// balance of `from` and doesn't write anything.
+    pub fn transfer_balance(
+        &mut self, from: &Address, to: &Address, amount: &U256,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    {
+        let maybe_from_account = self.get_account(from)?;
+        let from_balance = maybe_from_account
+            .as_ref()
+            .map_or(U256::zero(), |account| account.balance);
+        if from_balance < *amount {
+            return Err(ErrorKind::InsufficientBalance(
+                *from,
+                *amount,
+                from_balance,
+            )
+            .into());
+        }
+        if from == to || amount.is_zero() {
+            return Ok(());
+        }
+        // The balance check above guarantees that the account exists.
+        let original_from_account = maybe_from_account.unwrap();
+        let mut from_account = original_from_account.clone();
+
+        let mut to_account = match self.get_account(to)? {
+            Some(account) => account,
+            None => Account::new_empty_with_balance(
+                to,
+                &U256::zero(),
+                &U256::zero(),
+            ),
+        };
+        to_account.balance = match to_account.balance.checked_add(*amount) {
+            Some(balance) => balance,
+            None => return Err(ErrorKind::BalanceOverflow(*to).into()),
+        };
+        from_account.balance = from_balance - *amount;
+
+        self.set::<Account>(
+            StorageKey::new_account_key(from),
+            &from_account,
+            debug_record.as_deref_mut(),
+        )?;
+        if let Err(e) = self.set::<Account>(
+            StorageKey::new_account_key(to),
+            &to_account,
+            debug_record.as_deref_mut(),
+        ) {
+            self.set::<Account>(
+                StorageKey::new_account_key(from),
+                &original_from_account,
+                debug_record,
+            )?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Write the account, code and storage layout of a new contract, in this
+    /// order. The code hash of the account is checked against the code
+    /// before anything is written, so a mismatch doesn't leave a partially
+    /// created contract behind.
+    pub fn create_contract(
+        &mut self, address: &Address, account: &Account, code: &CodeInfo,
+        layout: &StorageLayout,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    {
+        debug_assert_eq!(account.address, *address);
+        let code_hash = keccak(&code.code);
+        if account.code_hash != code_hash {
+            return Err(ErrorKind::CodeHashMismatch(
+                *address,
+                account.code_hash,
+                code_hash,
+            )
+            .into());
+        }
+        self.set::<Account>(
+            StorageKey::new_account_key(address),
+            account,
+            debug_record.as_deref_mut(),
+        )?;
+        self.set::<CodeInfo>(
+            StorageKey::new_code_key(address, &code_hash),
+            code,
+            debug_record.as_deref_mut(),
+        )?;
+        self.set_storage_layout(address, layout, debug_record)
+    }
+
+    /// The result is memoized per address until the storage of the address
+    /// is written or the state root is computed.
     pub fn get_storage_root(
         &self, address: &Address,
     ) -> Result<Option<StorageRoot>> {
+        if let Some(root) = self.storage_root_cache.lock().get(address) {
+            return Ok(root.clone());
+        }
         let key = StorageKey::new_storage_root_key(address);
 
-        match self.storage.get_node_merkle_all_versions(key)? {
-            (None, None, None) => Ok(None),
+        let root = match self.storage.get_node_merkle_all_versions(key)? {
+            (None, None, None) => None,
             (maybe_delta, maybe_intermediate, maybe_snapshot) => {
-                Ok(Some(StorageRoot {
+                Some(StorageRoot {
                     delta: maybe_delta.unwrap_or(MERKLE_NULL_NODE),
                     intermediate: maybe_intermediate
                         .unwrap_or(MERKLE_NULL_NODE),
                     snapshot: maybe_snapshot.unwrap_or(MERKLE_NULL_NODE),
-                }))
+                })
+            }
+        };
+        self.storage_root_cache.lock().insert(*address, root.clone());
+        Ok(root)
+    }
+
+    /// Compute a reproducible digest over the subtrees of the given accounts,
+    /// e.g. to verify a partition of the state independently. This is not the
+    /// global state root. For every distinct address in sorted order, the
+    /// address and the merkle roots of its account subtree in the delta,
+    /// intermediate and snapshot tries are folded into a single keccak hash.
+    pub fn partial_state_root(&self, addresses: &[Address]) -> Result<H256> {
+        let mut sorted_addresses = addresses.to_vec();
+        sorted_addresses.sort();
+        sorted_addresses.dedup();
+
+        let mut buffer = Vec::new();
+        for address in &sorted_addresses {
+            let (maybe_delta, maybe_intermediate, maybe_snapshot) = self
+                .storage
+                .get_node_merkle_all_versions(StorageKey::new_account_key(
+                    address,
+                ))?;
+            buffer.extend_from_slice(address.as_bytes());
+            for maybe_merkle in
+                &[maybe_delta, maybe_intermediate, maybe_snapshot]
+            {
+                buffer.extend_from_slice(
+                    maybe_merkle.unwrap_or(MERKLE_NULL_NODE).as_bytes(),
+                );
             }
         }
+        Ok(keccak(&buffer))
+    }
+
+    /// Read the storage entries of an account whose storage keys fall in
+    /// `[start_key, end_key)`, in sorted key order and at most `limit` of
+    /// them. The storage layout isn't part of the result. The snapshot is
+    /// read by the range and stops at `limit`, while the recent changes in
+    /// the tries are still read in full for the account.
+    pub fn get_storage_range(
+        &self, address: &Address, start_key: &[u8], end_key: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Box<[u8]>)>>
+    {
+        let storage_root_key = StorageKey::new_storage_root_key(address);
+        let storage_key_offset = storage_root_key.to_key_bytes().len();
+        // The storage layout is stored under the empty storage key, which
+        // sorts before every other storage key.
+        let start_key = if start_key.is_empty() {
+            &[0u8][..]
+        } else {
+            start_key
+        };
+        Ok(self
+            .storage
+            .read_range(storage_root_key, start_key, end_key, limit)?
+            .into_iter()
+            .map(|(key, value)| (key[storage_key_offset..].to_vec(), value))
+            .collect())
+    }
+
+    /// Read all the storage entries of an account as (storage key, raw
+    /// value) pairs, sorted by key. The storage layout, the code and the
+    /// account entry aren't part of the result. The whole storage of the
+    /// account is collected in memory, which is heavy for contracts with a
+    /// large storage.
+    pub fn get_account_storage(
+        &self, address: &Address,
+    ) -> Result<Vec<(Vec<u8>, Box<[u8]>)>> {
+        let storage_root_key = StorageKey::new_storage_root_key(address);
+        let storage_key_offset = storage_root_key.to_key_bytes().len();
+        Ok(self
+            .read_all(storage_root_key)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| (key[storage_key_offset..].to_vec(), value))
+            .filter(|(key, _)| !key.is_empty())
+            .collect())
+    }
+
+    /// Export the complete state of an account, including its code, staking
+    /// lists, storage and signal/slot state, so that it can be imported into
+    /// another state with `import_account`.
+    pub fn export_account(
+        &self, address: &Address,
+    ) -> Result<Option<PortableAccount>> {
+        let account = match self.get_account(address)? {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        let code = self.get_code(address, &account.code_hash)?;
+
+        let storage_root_key = StorageKey::new_storage_root_key(address);
+        let storage_key_offset = storage_root_key.to_key_bytes().len();
+        let storage = self
+            .read_all(storage_root_key)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| {
+                (key[storage_key_offset..].to_vec(), value.into_vec())
+            })
+            .collect();
+        let (deposit_list, vote_stake_list) =
+            self.get_staking_lists(address)?;
+
+        Ok(Some(PortableAccount {
+            account,
+            code,
+            deposit_list,
+            vote_stake_list,
+            storage,
+            //////////////////////////////////////////////////////////////////////
+            /* Signal and Slots begin */
+            signal_infos: self.get_account_signal_infos(address)?,
+            slot_infos: self.get_account_slot_infos(address)?,
+            slot_tx_queue: self.get_account_slot_tx_queue(address)?,
+            /* Signal and Slots end */
+            //////////////////////////////////////////////////////////////////////
+        }))
+    }
+
+    /// Write back an account exported by `export_account`. Existing entries
+    /// of the account are overwritten, but entries which are not part of
+    /// `portable` are kept, so it should be imported into a state where the
+    /// account doesn't exist yet.
+    pub fn import_account(
+        &mut self, portable: &PortableAccount,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    {
+        let address = portable.account.address;
+        self.set::<Account>(
+            StorageKey::new_account_key(&address),
+            &portable.account,
+            debug_record.as_deref_mut(),
+        )?;
+        if let Some(code) = &portable.code {
+            self.set::<CodeInfo>(
+                StorageKey::new_code_key(&address, &portable.account.code_hash),
+                code,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+        if let Some(deposit_list) = &portable.deposit_list {
+            self.set::<DepositList>(
+                StorageKey::new_deposit_list_key(&address),
+                deposit_list,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+        if let Some(vote_stake_list) = &portable.vote_stake_list {
+            self.set::<VoteStakeList>(
+                StorageKey::new_vote_list_key(&address),
+                vote_stake_list,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+        for (key, value) in &portable.storage {
+            let storage_key = if key.is_empty() {
+                StorageKey::new_storage_root_key(&address)
+            } else {
+                StorageKey::new_storage_key(&address, key)
+            };
+            self.set_raw(
+                storage_key,
+                value.clone().into_boxed_slice(),
+                debug_record.as_deref_mut(),
+            )?;
+        }
+
+        //////////////////////////////////////////////////////////////////////
+        /* Signal and Slots begin */
+        for sig_info in &portable.signal_infos {
+            self.set_signal_info(
+                &address,
+                sig_info.location().signal_key(),
+                sig_info,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+        for slot_info in &portable.slot_infos {
+            self.set_slot_info(
+                &address,
+                slot_info.location().slot_key(),
+                slot_info,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+        if let Some(slot_tx_queue) = &portable.slot_tx_queue {
+            self.set_account_slot_tx_queue(
+                &address,
+                slot_tx_queue,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+        /* Signal and Slots end */
+        //////////////////////////////////////////////////////////////////////
+
+        Ok(())
     }
 
+    /// Read the current value of a key. Writes staged with `set_raw` and
+    /// `delete` go into the in-memory delta trie, which is looked up before
+    /// the intermediate trie and the snapshot, so they are visible here
+    /// before `commit`. A staged delete reads as `None`.
     pub fn get_raw(&self, key: StorageKey) -> Result<Option<Box<[u8]>>> {
         let r = Ok(self.storage.get(key)?);
         trace!("get_raw key={:?}, value={:?}", key, r);
         r
     }
 
+    /// The length in bytes of the value of a key, without decoding it.
+    pub fn get_raw_len(&self, key: StorageKey) -> Result<Option<usize>> {
+        Ok(self.get_raw(key)?.map(|raw| raw.len()))
+    }
+
+    /// Read the raw values of several storage entries of a contract, in the
+    /// order of `slot_keys`, with `None` for the missing ones. The storage
+    /// has no batched lookup, so the entries are read one by one; lookups of
+    /// keys under the same contract share the upper trie nodes in the node
+    /// cache.
+    pub fn get_contract_slots(
+        &self, address: &Address, slot_keys: &[Vec<u8>],
+    ) -> Result<Vec<Option<Box<[u8]>>>> {
+        slot_keys
+            .iter()
+            .map(|slot_key| {
+                self.get_raw(StorageKey::new_storage_key(address, slot_key))
+            })
+            .collect()
+    }
+
     pub fn get_raw_with_proof(
         &self, key: StorageKey,
     ) -> Result<(Option<Box<[u8]>>, StateProof)> {
@@ -151,6 +890,24 @@ impl StateDb {
         self.set_raw(key, ::rlp::encode(value).into_boxed_slice(), debug_record)
     }
 
+    /// Write `new` only if the current value of the key equals `expected`,
+    /// where `None` expects the key to be missing. Returns whether the value
+    /// was written. The StateDb is borrowed mutably throughout, so nothing
+    /// else can write the key between the read and the write.
+    pub fn set_if<T>(
+        &mut self, key: StorageKey, expected: Option<&T>, new: &T,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<bool>
+    where
+        T: ::rlp::Encodable + ::rlp::Decodable + PartialEq,
+    {
+        if self.get::<T>(key)?.as_ref() != expected {
+            return Ok(false);
+        }
+        self.set::<T>(key, new, debug_record)?;
+        Ok(true)
+    }
+
     pub fn set_raw(
         &mut self, key: StorageKey, value: Box<[u8]>,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
@@ -163,6 +920,18 @@ impl StateDb {
                 maybe_value: Some(value.clone().into()),
             })
         }
+        self.touch(&key);
+        if let Some(changed_keys) = self.changed_keys.as_mut() {
+            changed_keys.insert(key.to_key_bytes());
+        }
+        self.record_undo(&key)?;
+        self.bytes_written =
+            self.bytes_written.saturating_add(value.len() as u64);
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.writes += 1;
+            metrics.bytes_written =
+                metrics.bytes_written.saturating_add(value.len() as u64);
+        }
         match self.storage.set(key, value) {
             Ok(_) => Ok(()),
             Err(StorageError(StorageErrorKind::MPTKeyNotFound, _)) => Ok(()),
@@ -182,6 +951,14 @@ impl StateDb {
                 maybe_value: None,
             })
         }
+        self.touch(&key);
+        if let Some(changed_keys) = self.changed_keys.as_mut() {
+            changed_keys.insert(key.to_key_bytes());
+        }
+        self.record_undo(&key)?;
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.deletes += 1;
+        }
         match self.storage.delete(key) {
             Ok(_) => Ok(()),
             Err(e) => Err(e.into()),
@@ -200,7 +977,198 @@ impl StateDb {
                 maybe_value: None,
             })
         }
-        Ok(self.storage.delete_all(key_prefix)?)
+        self.touch(&key_prefix);
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.deletes += 1;
+        }
+        let deleted = self.storage.delete_all(key_prefix)?;
+        if let (Some(changed_keys), Some(deleted)) =
+            (self.changed_keys.as_mut(), &deleted)
+        {
+            for (key, _) in deleted {
+                changed_keys.insert(key.clone());
+            }
+        }
+        if let (Some(undo_log), Some(deleted)) =
+            (self.checkpoints.last_mut(), &deleted)
+        {
+            for (key, value) in deleted {
+                undo_log.push((key.clone(), Some(value.clone())));
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Take a checkpoint of the pending writes. The writes made afterwards
+    /// can be undone by `revert_to` without committing. Checkpoints nest
+    /// with a stack discipline, and they are all dropped on commit. The
+    /// change counters of `epoch_change_summary` are not reverted.
+    pub fn checkpoint(&mut self) -> CheckpointId {
+        self.checkpoints.push(Vec::new());
+        CheckpointId(self.checkpoints.len() - 1)
+    }
+
+    /// Undo the writes made since the checkpoint `id`, dropping it and the
+    /// checkpoints taken after it.
+    pub fn revert_to(&mut self, id: CheckpointId) -> Result<()> {
+        if id.0 >= self.checkpoints.len() {
+            return Err(ErrorKind::UnknownCheckpoint(id.0).into());
+        }
+        while self.checkpoints.len() > id.0 {
+            let undo_log = self.checkpoints.pop().unwrap_or_default();
+            for (key_bytes, value) in undo_log.into_iter().rev() {
+                let key = StorageKey::from_key_bytes(&key_bytes);
+                self.touch(&key);
+                if let Some(changed_keys) = self.changed_keys.as_mut() {
+                    changed_keys.insert(key_bytes.clone());
+                }
+                match value {
+                    Some(value) => match self.storage.set(key, value) {
+                        Ok(_) => {}
+                        Err(StorageError(
+                            StorageErrorKind::MPTKeyNotFound,
+                            _,
+                        )) => {}
+                        Err(e) => return Err(e.into()),
+                    },
+                    None => self.storage.delete(key)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Remember the current value of key for the innermost checkpoint.
+    fn record_undo(&mut self, key: &StorageKey) -> Result<()> {
+        if self.checkpoints.is_empty() {
+            return Ok(());
+        }
+        let value = self.storage.get(*key)?;
+        if let Some(undo_log) = self.checkpoints.last_mut() {
+            undo_log.push((key.to_key_bytes(), value));
+        }
+        Ok(())
+    }
+
+    /// Delete all entries under a key prefix like `delete_all`, and return
+    /// only the number of entries deleted. The storage still collects the
+    /// deleted entries, but they are dropped here instead of being handed to
+    /// the caller.
+    pub fn delete_all_count(
+        &mut self, key_prefix: StorageKey,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<usize>
+    {
+        Ok(self
+            .delete_all(key_prefix, debug_record)?
+            .map_or(0, |deleted| deleted.len()))
+    }
+
+    // Every storage key starts with the address it belongs to. A prefix
+    // shorter than an address may cover any address.
+    fn touch(&mut self, key: &StorageKey) {
+        let mut address = key.to_key_bytes();
+        address.truncate(StorageKey::ACCOUNT_BYTES);
+        let storage_root_cache = self.storage_root_cache.get_mut();
+        if address.len() == StorageKey::ACCOUNT_BYTES {
+            storage_root_cache.remove(&Address::from_slice(&address));
+        } else {
+            storage_root_cache.clear();
+        }
+        self.touched_addresses.insert(address);
+    }
+
+    /// Summarize the changes made since the last commit. The counters are
+    /// kept while writing, so this doesn't read the storage.
+    pub fn epoch_change_summary(&self) -> EpochChangeSummary {
+        EpochChangeSummary {
+            accounts_touched: self.touched_addresses.len(),
+            bytes_written: self.bytes_written,
+            slot_txs_enqueued: self.slot_txs_enqueued,
+        }
+    }
+
+    pub fn read_all(
+        &self, key_prefix: StorageKey,
+    ) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>> {
+        Ok(self.storage.read_all(key_prefix)?)
+    }
+
+    /// Read the key/value pairs under a key prefix, sorted by key, without
+    /// deleting them. The entries are found by the same traversal of the
+    /// tries and the snapshot as `delete_all`, which collects its results,
+    /// so all the entries under the prefix are held in memory. Unlike
+    /// `read_all`, no entries give an empty list rather than `None`.
+    pub fn iter_prefix(
+        &self, key_prefix: StorageKey,
+    ) -> Result<Vec<(Vec<u8>, Box<[u8]>)>> {
+        Ok(self.read_all(key_prefix)?.unwrap_or_default())
+    }
+
+    /// List the keys under a key prefix whose raw values differ between this
+    /// state and `other`, including the keys present on one side only,
+    /// sorted by key. Both sides are read with `read_all`, so this is meant
+    /// for offline reconciliation of forks rather than hot paths.
+    pub fn diff(
+        &self, other: &StateDb, key_prefix: StorageKey,
+    ) -> Result<Vec<StorageDiffEntry>> {
+        // (left, right) values by key.
+        let mut values = BTreeMap::new();
+        for (key, value) in self.read_all(key_prefix)?.unwrap_or_default() {
+            values.insert(key, (Some(value), None));
+        }
+        for (key, value) in other.read_all(key_prefix)?.unwrap_or_default() {
+            values.entry(key).or_insert((None, None)).1 = Some(value);
+        }
+        Ok(values
+            .into_iter()
+            .filter(|(_, (left, right))| left != right)
+            .map(|(key, (left, right))| StorageDiffEntry { key, left, right })
+            .collect())
+    }
+
+    /// Read every key/value pair in the state, sorted by key. This is a full
+    /// scan of the state.
+    pub fn read_all_state(
+        &self,
+    ) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>> {
+        Ok(self.storage.read_all_state()?)
+    }
+
+    /// Compute the storage collateral implied by the storage entries of all
+    /// accounts, i.e. the number of storage keys times
+    /// `COLLATERAL_PER_STORAGE_KEY`, so that it can be compared against
+    /// `get_total_storage_tokens` to detect accounting drift. Entries which
+    /// the state db writes for its own bookkeeping aren't charged and are
+    /// excluded, and so is the collateral for code. This is a full scan of
+    /// the state.
+    pub fn compute_total_storage_collateral(&self) -> Result<U256> {
+        let mut storage_entries = U512::zero();
+        for (key, _) in self.read_all_state()?.unwrap_or_default() {
+            if let StorageKey::StorageKey { address_bytes, .. } =
+                StorageKey::from_key_bytes(&key)
+            {
+                if !Self::is_bookkeeping_address(&Address::from_slice(
+                    address_bytes,
+                )) {
+                    storage_entries = storage_entries + U512::one();
+                }
+            }
+        }
+        let total = storage_entries * U512::from(*COLLATERAL_PER_STORAGE_KEY);
+        Ok(U256::try_from(total).unwrap_or(U256::max_value()))
+    }
+
+    // Addresses whose storage holds global values written by the state db
+    // itself rather than by contracts.
+    fn is_bookkeeping_address(address: &Address) -> bool {
+        *address == *STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS
+            //////////////////////////////////////////////////////////////////////
+            /* Signal and Slots begin */
+            || *address == *GLOBAL_SLOT_TX_QUEUE_ADDRESS
+            || *address == *GLOBAL_SLOT_TX_ACCOUNT_LIST_ADDRESS
+            /* Signal and Slots end */
+            //////////////////////////////////////////////////////////////////////
     }
 
     /// This method is only used for genesis block because state root is
@@ -208,16 +1176,44 @@ impl StateDb {
     /// deferred execution so the state root computation is merged inside
     /// commit method.
     pub fn compute_state_root(&mut self) -> Result<StateRootWithAuxInfo> {
+        self.storage_root_cache.get_mut().clear();
         Ok(self.storage.compute_state_root()?)
     }
 
     pub fn commit(
         &mut self, epoch_id: EpochId,
     ) -> Result<StateRootWithAuxInfo> {
+        Ok(self.commit_with_changeset(epoch_id)?.0)
+    }
+
+    /// Commit like `commit`, and also return the keys set or deleted since
+    /// the last commit, sorted. The storage doesn't report the keys it
+    /// commits, so they are collected by `set_raw`, `delete` and
+    /// `delete_all` once `enable_changeset_tracking` is called, and the list
+    /// is empty before that. A key may be listed even if its value ends up
+    /// unchanged, e.g. when it's set to its previous value or the write is
+    /// reverted by `revert_to`.
+    pub fn commit_with_changeset(
+        &mut self, epoch_id: EpochId,
+    ) -> Result<(StateRootWithAuxInfo, Vec<Vec<u8>>)> {
         let result = self.compute_state_root();
         self.storage.commit(epoch_id)?;
 
-        result
+        debug!(
+            "commit epoch_id={:?}, changes={:?}",
+            epoch_id,
+            self.epoch_change_summary()
+        );
+        self.touched_addresses.clear();
+        self.bytes_written = 0;
+        self.slot_txs_enqueued = 0;
+        self.checkpoints.clear();
+        let changed_keys = match self.changed_keys.as_mut() {
+            Some(changed_keys) => mem::take(changed_keys).into_iter().collect(),
+            None => Vec::new(),
+        };
+
+        Ok((result?, changed_keys))
     }
 
     pub fn get_annual_interest_rate(&self) -> Result<U256> {
@@ -240,6 +1236,27 @@ impl StateDb {
         Ok(acc_interest_rate_opt.unwrap_or(*ACCUMULATED_INTEREST_RATE_SCALE))
     }
 
+    /// Read the annual and the accumulated interest rates as stored, with
+    /// `None` for a rate which was never written, unlike
+    /// `get_annual_interest_rate` and `get_accumulate_interest_rate` which
+    /// fall back to the defaults.
+    pub fn get_interest_rates_raw(
+        &self,
+    ) -> Result<(Option<U256>, Option<U256>)> {
+        let interest_rate_key = StorageKey::new_storage_key(
+            &STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
+            Self::INTEREST_RATE_KEY,
+        );
+        let acc_interest_rate_key = StorageKey::new_storage_key(
+            &STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
+            Self::ACCUMULATE_INTEREST_RATE_KEY,
+        );
+        Ok((
+            self.get::<U256>(interest_rate_key)?,
+            self.get::<U256>(acc_interest_rate_key)?,
+        ))
+    }
+
     pub fn get_total_issued_tokens(&self) -> Result<U256> {
         let total_issued_tokens_key = StorageKey::new_storage_key(
             &STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS,
@@ -270,6 +1287,89 @@ impl StateDb {
         Ok(total_storage_tokens_opt.unwrap_or(U256::zero()))
     }
 
+    /// Read all the global staking values, with the same defaults as their
+    /// individual getters.
+    pub fn get_global_statistics(&self) -> Result<GlobalStatistics> {
+        let address = &*STORAGE_INTEREST_STAKING_CONTRACT_ADDRESS;
+        let keys = [
+            StorageKey::new_storage_key(address, Self::TOTAL_TOKENS_KEY),
+            StorageKey::new_storage_key(address, Self::TOTAL_BANK_TOKENS_KEY),
+            StorageKey::new_storage_key(
+                address,
+                Self::TOTAL_STORAGE_TOKENS_KEY,
+            ),
+            StorageKey::new_storage_key(address, Self::INTEREST_RATE_KEY),
+            StorageKey::new_storage_key(
+                address,
+                Self::ACCUMULATE_INTEREST_RATE_KEY,
+            ),
+        ];
+        let values = self.get_many::<U256>(&keys)?;
+        Ok(GlobalStatistics {
+            total_issued: values[0].unwrap_or_default(),
+            total_staking: values[1].unwrap_or_default(),
+            total_storage: values[2].unwrap_or_default(),
+            interest_rate: values[3].unwrap_or_else(|| {
+                *INITIAL_INTEREST_RATE_PER_BLOCK * U256::from(BLOCKS_PER_YEAR)
+            }),
+            accumulate_interest_rate: values[4]
+                .unwrap_or(*ACCUMULATED_INTEREST_RATE_SCALE),
+        })
+    }
+
+    pub fn set_global_statistics(
+        &mut self, statistics: &GlobalStatistics,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    {
+        self.set_annual_interest_rate(
+            &statistics.interest_rate,
+            debug_record.as_deref_mut(),
+        )?;
+        self.set_accumulate_interest_rate(
+            &statistics.accumulate_interest_rate,
+            debug_record.as_deref_mut(),
+        )?;
+        self.set_total_issued_tokens(
+            &statistics.total_issued,
+            debug_record.as_deref_mut(),
+        )?;
+        self.set_total_staking_tokens(
+            &statistics.total_staking,
+            debug_record.as_deref_mut(),
+        )?;
+        self.set_total_storage_tokens(&statistics.total_storage, debug_record)
+    }
+
+    /// Check the invariant that the total staking tokens equal the sum of the
+    /// amounts in the deposit lists of all accounts, since depositing and
+    /// withdrawing change both by the same amount. Returns the drift if they
+    /// disagree. The sum is computed in U512. This is a full scan of the
+    /// state.
+    ///
+    /// `State` keeps the total and the deposit lists in its caches and
+    /// writes both when it commits, so the invariant only holds for a state
+    /// which `State` has committed to. A deposit list which fails to decode
+    /// is an error rather than a drift.
+    pub fn verify_total_staking_tokens(&self) -> Result<Option<StakingDrift>> {
+        let mut deposited = U512::zero();
+        for item in self.iter_deposit_lists()? {
+            let (_, deposit_list) = item?;
+            for deposit_info in &deposit_list.0 {
+                deposited = deposited + U512::from(deposit_info.amount);
+            }
+        }
+        let recorded = self.get_total_staking_tokens()?;
+        if U512::from(recorded) == deposited {
+            Ok(None)
+        } else {
+            Ok(Some(StakingDrift {
+                recorded,
+                deposited,
+            }))
+        }
+    }
+
     pub fn set_annual_interest_rate(
         &mut self, interest_rate: &U256,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
@@ -349,13 +1449,30 @@ impl StateDb {
     //////////////////////////////////////////////////////////////////////
     /* Signal and Slots begin */
 
+    // The storage key of the list of contract addresses with ready slot
+    // transactions.
+    fn ready_slot_tx_list_key() -> StorageKey<'static> {
+        StorageKey::new_storage_key(
+            &GLOBAL_SLOT_TX_ACCOUNT_LIST_ADDRESS,
+            Self::SLOT_TX_READY_LIST_KEY,
+        )
+    }
+
+    // The global slot transaction queue of an epoch is keyed by the little
+    // endian epoch height. The StorageKey borrows the key bytes, so callers
+    // hold on to them.
+    fn global_slot_tx_queue_epoch_key(epoch_height: u64) -> [u8; 8] {
+        epoch_height.to_le_bytes()
+    }
+
+    fn global_slot_tx_queue_key(epoch_key: &[u8; 8]) -> StorageKey {
+        StorageKey::new_storage_key(&GLOBAL_SLOT_TX_QUEUE_ADDRESS, epoch_key)
+    }
+
     // Retrieve the list of contract addresses with pending slot transaction ready to be handled
     pub fn get_addresses_with_ready_slot_tx(&self)
     -> Result<Option<SlotTxAddressList>> {
-        let key = StorageKey::new_storage_key(
-            &GLOBAL_SLOT_TX_ACCOUNT_LIST_ADDRESS,
-            Self::SLOT_TX_READY_LIST_KEY,
-        );
+        let key = Self::ready_slot_tx_list_key();
         self.get::<SlotTxAddressList>(key)
     }
 
@@ -363,44 +1480,111 @@ impl StateDb {
         &mut self, accounts: &SlotTxAddressList,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
     ) -> Result<()> {
-        let key = StorageKey::new_storage_key(
-            &GLOBAL_SLOT_TX_ACCOUNT_LIST_ADDRESS,
-            Self::SLOT_TX_READY_LIST_KEY,
-        );
+        let key = Self::ready_slot_tx_list_key();
         self.set::<SlotTxAddressList>(key, accounts, debug_record)
     }
 
     pub fn delete_addresses_with_ready_slot_tx(
         &mut self, debug_record: Option<&mut ComputeEpochDebugRecord>,
     ) -> Result<()> {
-        let key = StorageKey::new_storage_key(
-            &GLOBAL_SLOT_TX_ACCOUNT_LIST_ADDRESS,
-            Self::SLOT_TX_READY_LIST_KEY,
-        );
+        let key = Self::ready_slot_tx_list_key();
         self.delete(key, debug_record)
     }
 
-    // Given an epoch number, retrieve the queue.
-    pub fn get_global_slot_tx_queue(
-        &self, epoch_height: u64,
-    ) -> Result<Option<SlotTxQueue>> {
-        let buffer = epoch_height.to_le_bytes();
-        let key = StorageKey::new_storage_key(
-            &GLOBAL_SLOT_TX_QUEUE_ADDRESS,
-            &buffer,
-        );
-        self.get::<SlotTxQueue>(key)
-    }
+    // Add an address to the list of addresses with ready slot transactions.
+    // The list isn't rewritten if it already contains the address.
+    pub fn add_ready_slot_tx_address(
+        &mut self, address: &Address,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()> {
+        let mut ready_list = self
+            .get_addresses_with_ready_slot_tx()?
+            .unwrap_or_else(SlotTxAddressList::new);
+        if ready_list.contains(address) {
+            return Ok(());
+        }
+        ready_list.add(address);
+        self.set_addresses_with_ready_slot_tx(&ready_list, debug_record)
+    }
 
+    // Remove an address from the list of addresses with ready slot
+    // transactions, deleting the list once it's empty. Does nothing if the
+    // address isn't in the list.
+    pub fn remove_ready_slot_tx_address(
+        &mut self, address: &Address,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()> {
+        let mut ready_list = match self.get_addresses_with_ready_slot_tx()? {
+            Some(ready_list) => ready_list,
+            None => return Ok(()),
+        };
+        if !ready_list.contains(address) {
+            return Ok(());
+        }
+        ready_list.remove(address);
+        if ready_list.is_empty() {
+            self.delete_addresses_with_ready_slot_tx(debug_record)
+        } else {
+            self.set_addresses_with_ready_slot_tx(&ready_list, debug_record)
+        }
+    }
+
+    // Given an epoch number, retrieve the queue.
+    pub fn get_global_slot_tx_queue(
+        &self, epoch_height: u64,
+    ) -> Result<Option<SlotTxQueue>> {
+        let epoch_key = Self::global_slot_tx_queue_epoch_key(epoch_height);
+        let key = Self::global_slot_tx_queue_key(&epoch_key);
+        self.get::<SlotTxQueue>(key)
+    }
+
+    // Read the non-empty global queues of the epochs from start to end
+    // inclusive, in height order. The queues are keyed by little endian
+    // height, which doesn't sort by height, so all the global queues are
+    // read and then filtered.
+    pub fn get_global_slot_tx_queues_in_range(
+        &self, start: u64, end: u64,
+    ) -> Result<Vec<(u64, SlotTxQueue)>> {
+        let prefix =
+            StorageKey::new_storage_root_key(&GLOBAL_SLOT_TX_QUEUE_ADDRESS);
+        let mut queues = Vec::new();
+        for (key, raw) in self.read_all(prefix)?.unwrap_or_default() {
+            let epoch_height = match StorageKey::from_key_bytes(&key) {
+                StorageKey::StorageKey { storage_key, .. } => {
+                    match <[u8; 8]>::try_from(storage_key) {
+                        Ok(epoch_key) => u64::from_le_bytes(epoch_key),
+                        Err(_) => continue,
+                    }
+                }
+                _ => continue,
+            };
+            if epoch_height < start || epoch_height > end {
+                continue;
+            }
+            let queue = ::rlp::decode::<SlotTxQueue>(raw.as_ref())
+                .map_err(|e| ErrorKind::DecodeError(key.clone(), e))?;
+            if !queue.is_empty() {
+                queues.push((epoch_height, queue));
+            }
+        }
+        queues.sort_by_key(|(epoch_height, _)| *epoch_height);
+        Ok(queues)
+    }
+
+    // Slot transactions are only ever appended to a global queue before
+    // it is drained, so the growth of the queue is the number enqueued.
     pub fn set_global_slot_tx_queue(
         &mut self, epoch_height: u64, queue: &SlotTxQueue,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
     ) -> Result<()> {
-        let buffer = epoch_height.to_le_bytes();
-        let key = StorageKey::new_storage_key(
-            &GLOBAL_SLOT_TX_QUEUE_ADDRESS,
-            &buffer,
-        );
+        let old_len = self
+            .get_global_slot_tx_queue(epoch_height)?
+            .map_or(0, |old_queue| old_queue.len());
+        self.slot_txs_enqueued = self
+            .slot_txs_enqueued
+            .saturating_add(queue.len().saturating_sub(old_len) as u64);
+        let epoch_key = Self::global_slot_tx_queue_epoch_key(epoch_height);
+        let key = Self::global_slot_tx_queue_key(&epoch_key);
         self.set::<SlotTxQueue>(key, queue, debug_record)
     }
 
@@ -408,11 +1592,8 @@ impl StateDb {
         &mut self, epoch_height: u64,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
     ) -> Result<()> {
-        let buffer = epoch_height.to_le_bytes();
-        let key = StorageKey::new_storage_key(
-            &GLOBAL_SLOT_TX_QUEUE_ADDRESS,
-            &buffer,
-        );
+        let epoch_key = Self::global_slot_tx_queue_epoch_key(epoch_height);
+        let key = Self::global_slot_tx_queue_key(&epoch_key);
         self.delete(key, debug_record)
     }
 
@@ -422,6 +1603,23 @@ impl StateDb {
         self.get::<SlotTxQueue>(StorageKey::new_slot_tx_queue_key(address))
     }
 
+    // The number of transactions in the slot transaction queue of an account.
+    // Only the list headers of the RLP are walked, the transactions aren't
+    // decoded.
+    pub fn get_account_slot_tx_queue_len(
+        &self, address: &Address,
+    ) -> Result<Option<usize>> {
+        let key = StorageKey::new_slot_tx_queue_key(address);
+        let raw = match self.get_raw(key)? {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        match ::rlp::Rlp::new(raw.as_ref()).item_count() {
+            Ok(count) => Ok(Some(count)),
+            Err(e) => Err(ErrorKind::DecodeError(key.to_key_bytes(), e).into()),
+        }
+    }
+
     pub fn set_account_slot_tx_queue(
         &mut self, address: &Address, slot_tx_queue: &SlotTxQueue,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
@@ -443,12 +1641,190 @@ impl StateDb {
         )
     }
 
+    // Find all accounts which actually have queued slot transactions, to
+    // cross check the maintained list of addresses with ready slot
+    // transactions. This is a full scan of the state.
+    pub fn scan_accounts_with_slot_tx_queue(&self) -> Result<Vec<Address>> {
+        let mut addresses = Vec::new();
+        for (key, raw) in self.read_all_state()?.unwrap_or_default() {
+            if let StorageKey::SlotTxQueueKey(address_bytes) =
+                StorageKey::from_key_bytes(&key)
+            {
+                if !::rlp::decode::<SlotTxQueue>(raw.as_ref())?.is_empty() {
+                    addresses.push(Address::from_slice(address_bytes));
+                }
+            }
+        }
+        Ok(addresses)
+    }
+
+    // Every address in the list of addresses with ready slot transactions
+    // should have a non-empty queue. Return the addresses violating this
+    // invariant, i.e. those whose queue is empty or missing.
+    pub fn verify_ready_list_invariant(&self) -> Result<Vec<Address>> {
+        let mut violations = Vec::new();
+        if let Some(ready_list) = self.get_addresses_with_ready_slot_tx()? {
+            for address in ready_list.get_list() {
+                let has_slot_tx = self
+                    .get_account_slot_tx_queue(address)?
+                    .map_or(false, |queue| !queue.is_empty());
+                if !has_slot_tx {
+                    violations.push(*address);
+                }
+            }
+        }
+        Ok(violations)
+    }
+
+    // Retrieve the sorted distinct epoch heights of the slot transactions
+    // in the queue of an account.
+    pub fn account_pending_epochs(&self, address: &Address) -> Result<Vec<u64>> {
+        let mut epochs = Vec::new();
+        if let Some(queue) = self.get_account_slot_tx_queue(address)? {
+            epochs = queue.iter().map(|tx| tx.epoch_height()).collect();
+            epochs.sort();
+            epochs.dedup();
+        }
+        Ok(epochs)
+    }
+
+    // Find duplicated slot transactions in the queue of an account. Each
+    // duplicate is reported as (index of first occurrence, index of
+    // duplicate), ordered by the index of the duplicate.
+    pub fn find_duplicate_slot_txs(
+        &self, address: &Address,
+    ) -> Result<Vec<(usize, usize)>> {
+        Ok(self
+            .get_account_slot_tx_queue(address)?
+            .map_or_else(Vec::new, |queue| Self::duplicate_slot_txs(&queue)))
+    }
+
+    // Find the duplicates of find_duplicate_slot_txs in a queue. The
+    // transactions are keyed on the identity compared by
+    // SlotTx::is_duplicated, so this is a single pass over the queue.
+    fn duplicate_slot_txs(queue: &SlotTxQueue) -> Vec<(usize, usize)> {
+        let mut first_indices = HashMap::new();
+        let mut duplicates = Vec::new();
+        for (idx, tx) in queue.iter().enumerate() {
+            let identity = (tx.location(), tx.raw_data(), tx.epoch_height());
+            match first_indices.get(&identity) {
+                Some(&first) => duplicates.push((first, idx)),
+                None => {
+                    first_indices.insert(identity, idx);
+                }
+            }
+        }
+        duplicates
+    }
+
+    // Remove duplicated slot transactions from the queue of an account,
+    // keeping the first occurrence of each. The cleaned queue is written
+    // back only if anything was removed. Returns the number of removed
+    // transactions.
+    pub fn dedup_account_slot_tx_queue(
+        &mut self, address: &Address,
+        debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<usize> {
+        let queue = match self.get_account_slot_tx_queue(address)? {
+            Some(queue) => queue,
+            None => return Ok(0),
+        };
+        let duplicates = Self::duplicate_slot_txs(&queue);
+        if duplicates.is_empty() {
+            return Ok(0);
+        }
+        let mut cleaned = SlotTxQueue::new();
+        let mut duplicate_indices =
+            duplicates.iter().map(|&(_, j)| j).peekable();
+        for (idx, tx) in queue.iter().enumerate() {
+            if duplicate_indices.peek() == Some(&idx) {
+                duplicate_indices.next();
+                continue;
+            }
+            cleaned.enqueue(tx.clone());
+        }
+        self.set_account_slot_tx_queue(address, &cleaned, debug_record)?;
+        Ok(duplicates.len())
+    }
+
+    // Remove the slot transactions of an account which are expired, i.e.
+    // whose epoch_height + grace < current_height. The grace window is the
+    // number of epochs a slot transaction may lag behind before it's
+    // considered dead rather than merely delayed, so it should be well above
+    // the usual scheduling delay. If the queue becomes empty, the account is
+    // also removed from the list of addresses with ready slot transactions.
+    // Returns the number of pruned transactions.
+    pub fn prune_expired_slot_txs(
+        &mut self, address: &Address, current_height: u64, grace: u64,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<usize>
+    {
+        let queue = match self.get_account_slot_tx_queue(address)? {
+            Some(queue) => queue,
+            None => return Ok(0),
+        };
+        let mut retained = SlotTxQueue::new();
+        for tx in queue.iter() {
+            if tx.epoch_height().saturating_add(grace) >= current_height {
+                retained.enqueue(tx.clone());
+            }
+        }
+        let pruned = queue.len() - retained.len();
+        if pruned == 0 {
+            return Ok(0);
+        }
+        self.set_account_slot_tx_queue(
+            address,
+            &retained,
+            debug_record.as_deref_mut(),
+        )?;
+
+        if retained.is_empty() {
+            self.remove_ready_slot_tx_address(address, debug_record)?;
+        }
+        Ok(pruned)
+    }
+
     pub fn get_signal_info(
         &self, address: &Address, signal_key: &Vec<u8>,
     ) -> Result<Option<SignalInfo>> {
         self.get::<SignalInfo>(StorageKey::new_signal_key(address, signal_key))
     }
 
+    // Read the SignalInfos of several signals of an account at once with
+    // get_many. The results are in the same order as signal_keys, with None
+    // for signals which don't exist, and a SignalInfo failing to decode is
+    // reported with its index in signal_keys.
+    pub fn get_signal_infos(
+        &self, address: &Address, signal_keys: &[Vec<u8>],
+    ) -> Result<Vec<Option<SignalInfo>>> {
+        let keys: Vec<StorageKey> = signal_keys
+            .iter()
+            .map(|signal_key| StorageKey::new_signal_key(address, signal_key))
+            .collect();
+        self.get_many::<SignalInfo>(&keys)
+    }
+
+    // Resolve the signals a slot is bound to, in the order of its bind list.
+    // A bound signal which no longer exists is logged and skipped rather
+    // than failing the whole call.
+    pub fn get_signals_for_slot(
+        &self, slot_info: &SlotInfo,
+    ) -> Result<Vec<SignalInfo>> {
+        let mut signals = Vec::with_capacity(slot_info.bind_list().len());
+        for loc in slot_info.bind_list() {
+            match self.get_signal_info(loc.address(), loc.signal_key())? {
+                Some(sig_info) => signals.push(sig_info),
+                None => warn!(
+                    "Slot {:?} is bound to missing signal {:?}",
+                    slot_info.location(),
+                    loc
+                ),
+            }
+        }
+        Ok(signals)
+    }
+
     pub fn set_signal_info(
         &mut self, address: &Address, signal_key: &Vec<u8>, sig_info: &SignalInfo,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
@@ -476,6 +1852,26 @@ impl StateDb {
         self.get::<SlotInfo>(StorageKey::new_slot_key(address, slot_key))
     }
 
+    // Build the slot transaction an emission of a signal at epoch_height
+    // would create for one of its slots, from the Slot stored in the slot
+    // list of the signal. None if the signal doesn't exist or the slot isn't
+    // bound to it. Signal arguments are opaque ABI encoded raw_data, so there
+    // is no argument count to check.
+    pub fn build_slot_tx(
+        &self, signal_address: &Address, signal_key: &Vec<u8>,
+        slot_location: &SlotLocation, epoch_height: u64, raw_data: &Vec<u8>,
+    ) -> Result<Option<SlotTx>> {
+        let sig_info = match self.get_signal_info(signal_address, signal_key)? {
+            Some(sig_info) => sig_info,
+            None => return Ok(None),
+        };
+        Ok(sig_info
+            .slot_list()
+            .iter()
+            .find(|slot| slot.location() == slot_location)
+            .map(|slot| SlotTx::new(slot, &epoch_height, raw_data)))
+    }
+
     pub fn set_slot_info(
         &mut self, address: &Address, slot_key: &Vec<u8>, slot_info: &SlotInfo,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
@@ -487,6 +1883,24 @@ impl StateDb {
         )
     }
 
+    // Write several SlotInfos of an account at once, in the given order,
+    // which is also the order of the state ops in the debug record.
+    pub fn set_slot_infos(
+        &mut self, address: &Address, slots: &[(Vec<u8>, SlotInfo)],
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<()>
+    {
+        for (slot_key, slot_info) in slots {
+            self.set_slot_info(
+                address,
+                slot_key,
+                slot_info,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+        Ok(())
+    }
+
     pub fn delete_slot_info(
         &mut self, address: &Address, slot_key: &Vec<u8>,
         debug_record: Option<&mut ComputeEpochDebugRecord>,
@@ -496,6 +1910,2276 @@ impl StateDb {
             debug_record,
         )
     }
+
+    // Retrieve all the signals owned by an account.
+    pub fn get_account_signal_infos(
+        &self, address: &Address,
+    ) -> Result<Vec<SignalInfo>> {
+        let mut sig_infos = Vec::new();
+        if let Some(kvs) =
+            self.read_all(StorageKey::new_signal_root_key(address))?
+        {
+            for (_, raw) in kvs {
+                sig_infos.push(::rlp::decode::<SignalInfo>(raw.as_ref())?);
+            }
+        }
+        Ok(sig_infos)
+    }
+
+    // Find the signals owned by an account whose slot list is longer than
+    // limit. Returns the signal keys with the length of their slot lists.
+    pub fn find_oversized_signals(
+        &self, address: &Address, limit: usize,
+    ) -> Result<Vec<(Vec<u8>, usize)>> {
+        Ok(self
+            .get_account_signal_infos(address)?
+            .into_iter()
+            .filter(|sig_info| sig_info.slot_list().len() > limit)
+            .map(|sig_info| {
+                (
+                    sig_info.location().signal_key().clone(),
+                    sig_info.slot_list().len(),
+                )
+            })
+            .collect())
+    }
+
+    // Retrieve all the slots owned by an account.
+    pub fn get_account_slot_infos(
+        &self, address: &Address,
+    ) -> Result<Vec<SlotInfo>> {
+        let mut slot_infos = Vec::new();
+        if let Some(kvs) =
+            self.read_all(StorageKey::new_slot_root_key(address))?
+        {
+            for (_, raw) in kvs {
+                slot_infos.push(::rlp::decode::<SlotInfo>(raw.as_ref())?);
+            }
+        }
+        Ok(slot_infos)
+    }
+
+    // Hash the signal and slot topology of an account: the signals with
+    // their slot lists and the slots with their bind lists. Everything is
+    // sorted first, so the fingerprint doesn't depend on the order in which
+    // the signals, slots and bindings were created.
+    pub fn signal_slot_fingerprint(&self, address: &Address) -> Result<H256> {
+        let mut sig_infos = self.get_account_signal_infos(address)?;
+        sig_infos.sort_by(|a, b| a.location().cmp(b.location()));
+        let mut slot_infos = self.get_account_slot_infos(address)?;
+        slot_infos.sort_by(|a, b| a.location().cmp(b.location()));
+
+        let mut stream = ::rlp::RlpStream::new_list(2);
+        stream.begin_list(sig_infos.len());
+        for sig_info in &sig_infos {
+            let mut slot_list = sig_info.slot_list().clone();
+            slot_list.sort();
+            stream
+                .begin_list(2)
+                .append(sig_info.location())
+                .append_list(&slot_list);
+        }
+        stream.begin_list(slot_infos.len());
+        for slot_info in &slot_infos {
+            let mut bind_list = slot_info.bind_list().clone();
+            bind_list.sort();
+            stream
+                .begin_list(2)
+                .append(slot_info.location())
+                .append_list(&bind_list);
+        }
+        Ok(keccak(stream.out()))
+    }
+
+    // Remove dangling references from the signals and slots owned by an
+    // account. Slots whose SlotInfo no longer exists are removed from the
+    // slot lists of the signals, signals whose SignalInfo no longer exists
+    // are removed from the bind lists of the slots, and a slot which loses
+    // all of its bindings this way is deleted. Slots that were never bound
+    // are left untouched.
+    pub fn gc_signal_slot_state(
+        &mut self, address: &Address,
+        mut debug_record: Option<&mut ComputeEpochDebugRecord>,
+    ) -> Result<GcReport> {
+        let mut report = GcReport::default();
+
+        for mut sig_info in self.get_account_signal_infos(address)? {
+            let mut dangling = Vec::new();
+            for slot in sig_info.slot_list() {
+                let loc = slot.location();
+                if self
+                    .get_slot_info(loc.address(), loc.slot_key())?
+                    .is_none()
+                {
+                    dangling.push(loc.clone());
+                }
+            }
+            if dangling.is_empty() {
+                continue;
+            }
+            for loc in dangling {
+                sig_info.remove_from_slot_list(&loc);
+                report
+                    .removed_slot_bindings
+                    .push((sig_info.location().clone(), loc));
+            }
+            let signal_key = sig_info.location().signal_key().clone();
+            self.set_signal_info(
+                address,
+                &signal_key,
+                &sig_info,
+                debug_record.as_deref_mut(),
+            )?;
+        }
+
+        for mut slot_info in self.get_account_slot_infos(address)? {
+            let mut dangling = Vec::new();
+            for loc in slot_info.bind_list() {
+                if self
+                    .get_signal_info(loc.address(), loc.signal_key())?
+                    .is_none()
+                {
+                    dangling.push(loc.clone());
+                }
+            }
+            if dangling.is_empty() {
+                continue;
+            }
+            for loc in dangling {
+                slot_info.remove_from_bind_list(&loc);
+                report
+                    .removed_signal_bindings
+                    .push((slot_info.location().clone(), loc));
+            }
+            let slot_key = slot_info.location().slot_key().clone();
+            if slot_info.bind_list().is_empty() {
+                self.delete_slot_info(
+                    address,
+                    &slot_key,
+                    debug_record.as_deref_mut(),
+                )?;
+                report.deleted_slots.push(slot_info.location().clone());
+            } else {
+                self.set_slot_info(
+                    address,
+                    &slot_key,
+                    &slot_info,
+                    debug_record.as_deref_mut(),
+                )?;
+            }
+        }
+
+        Ok(report)
+    }
+
+    // The distinct contracts whose queues an emission of the signal would
+    // enqueue slot transactions into, in the order of the slot list. Empty
+    // if the signal doesn't exist.
+    pub fn emission_touched_contracts(
+        &self, signal_address: &Address, signal_key: &Vec<u8>,
+    ) -> Result<Vec<Address>> {
+        let mut contracts = Vec::new();
+        if let Some(sig_info) =
+            self.get_signal_info(signal_address, signal_key)?
+        {
+            for slot in sig_info.slot_list() {
+                let address = slot.location().address();
+                if !contracts.contains(address) {
+                    contracts.push(*address);
+                }
+            }
+        }
+        Ok(contracts)
+    }
+
+    // Find the slots bound to a signal whose gas parameters were copied at
+    // binding time and no longer match the current SlotInfo. The contract
+    // owning the slot can re-bind to refresh them. Slots whose SlotInfo no
+    // longer exists are dangling rather than stale and are left to
+    // gc_signal_slot_state.
+    pub fn find_stale_slot_bindings(
+        &self, signal_address: &Address, signal_key: &Vec<u8>,
+    ) -> Result<Vec<SlotLocation>> {
+        let mut stale = Vec::new();
+        let sig_info = match self.get_signal_info(signal_address, signal_key)? {
+            Some(sig_info) => sig_info,
+            None => return Ok(stale),
+        };
+        for slot in sig_info.slot_list() {
+            let loc = slot.location();
+            if let Some(slot_info) =
+                self.get_slot_info(loc.address(), loc.slot_key())?
+            {
+                if slot.gas_limit() != slot_info.gas_limit()
+                    || slot.gas_ratio_numerator()
+                        != slot_info.gas_ratio_numerator()
+                    || slot.gas_ratio_denominator()
+                        != slot_info.gas_ratio_denominator()
+                {
+                    stale.push(loc.clone());
+                }
+            }
+        }
+        Ok(stale)
+    }
     /* Signal and Slots end */
     //////////////////////////////////////////////////////////////////////
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{
+        new_storage_manager_for_testing, tests::FakeStateManager,
+        StorageManagerTrait,
+    };
+    use cfx_types::address_util::AddressUtil;
+    use primitives::{DepositInfo, Slot, VoteStakeInfo};
+
+    // A state db on the genesis state of a new storage manager. The storage
+    // manager is boxed so that it stays in place, and has to outlive the
+    // state db.
+    fn new_state_db() -> (Box<FakeStateManager>, StateDb) {
+        let storage_manager = Box::new(new_storage_manager_for_testing());
+        let state_db =
+            StateDb::new(storage_manager.get_state_for_genesis_write());
+        (storage_manager, state_db)
+    }
+
+    // A slot of owner with the gas settings used throughout the tests.
+    fn new_test_slot_info(owner: &Address, slot_key: &[u8]) -> SlotInfo {
+        SlotInfo::new(
+            owner,
+            slot_key,
+            &H256::zero(),
+            owner,
+            &U256::from(1000),
+            &U256::from(100),
+        )
+    }
+
+    #[test]
+    fn test_global_statistics() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let defaults = state_db.get_global_statistics().unwrap();
+        assert_eq!(
+            defaults,
+            GlobalStatistics {
+                total_issued: state_db.get_total_issued_tokens().unwrap(),
+                total_staking: state_db.get_total_staking_tokens().unwrap(),
+                total_storage: state_db.get_total_storage_tokens().unwrap(),
+                interest_rate: state_db.get_annual_interest_rate().unwrap(),
+                accumulate_interest_rate: state_db
+                    .get_accumulate_interest_rate()
+                    .unwrap(),
+            }
+        );
+
+        let statistics = GlobalStatistics {
+            total_issued: U256::from(1),
+            total_staking: U256::from(2),
+            total_storage: U256::from(3),
+            interest_rate: U256::from(4),
+            accumulate_interest_rate: U256::from(5),
+        };
+        let mut debug_record = ComputeEpochDebugRecord::default();
+        state_db
+            .set_global_statistics(&statistics, Some(&mut debug_record))
+            .unwrap();
+        assert_eq!(debug_record.state_ops.len(), 5);
+        assert_eq!(state_db.get_global_statistics().unwrap(), statistics);
+        assert_eq!(state_db.get_total_storage_tokens().unwrap(), 3.into());
+        assert_eq!(state_db.get_annual_interest_rate().unwrap(), 4.into());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_delete_all_count() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let entries = [(&address, b"a"), (&address, b"b"), (&other, b"a")];
+        for (address, key) in &entries {
+            state_db
+                .set::<U256>(
+                    StorageKey::new_storage_key(address, *key),
+                    &U256::one(),
+                    None,
+                )
+                .unwrap();
+        }
+
+        let prefix = StorageKey::new_storage_root_key(&address);
+        let mut debug_record = ComputeEpochDebugRecord::default();
+        assert_eq!(
+            state_db
+                .delete_all_count(prefix, Some(&mut debug_record))
+                .unwrap(),
+            2
+        );
+        assert_eq!(debug_record.state_ops.len(), 1);
+        match &debug_record.state_ops[0] {
+            StateOp::StorageLevelOp {
+                op_name,
+                key,
+                maybe_value,
+            } => {
+                assert_eq!(op_name, "delete_all");
+                assert_eq!(*key, prefix.to_key_bytes());
+                assert!(maybe_value.is_none());
+            }
+            op => panic!("unexpected state op {:?}", op),
+        }
+        assert_eq!(state_db.delete_all_count(prefix, None).unwrap(), 0);
+        assert!(state_db
+            .get::<U256>(StorageKey::new_storage_key(&other, b"a"))
+            .unwrap()
+            .is_some());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_contract_slots() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        for (address, key, value) in
+            &[(&address, b"a", 1u8), (&address, b"c", 3u8), (&other, b"b", 2u8)]
+        {
+            state_db
+                .set_raw(
+                    StorageKey::new_storage_key(address, *key),
+                    vec![*value].into_boxed_slice(),
+                    None,
+                )
+                .unwrap();
+        }
+        let slot_keys = vec![b"c".to_vec(), b"b".to_vec(), b"a".to_vec()];
+        assert_eq!(
+            state_db.get_contract_slots(&address, &slot_keys).unwrap(),
+            vec![
+                Some(vec![3u8].into_boxed_slice()),
+                None,
+                Some(vec![1u8].into_boxed_slice()),
+            ]
+        );
+        assert!(state_db
+            .get_contract_slots(&address, &[])
+            .unwrap()
+            .is_empty());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_iter_prefix() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let entries = [(&address, b"b"), (&address, b"a"), (&other, b"a")];
+        for (address, key) in &entries {
+            state_db
+                .set::<U256>(
+                    StorageKey::new_storage_key(address, *key),
+                    &U256::from(key[0]),
+                    None,
+                )
+                .unwrap();
+        }
+        state_db
+            .delete(StorageKey::new_storage_key(&address, b"b"), None)
+            .unwrap();
+
+        let prefix = StorageKey::new_storage_root_key(&address);
+        let entries = state_db.iter_prefix(prefix).unwrap();
+        assert_eq!(
+            entries,
+            vec![(
+                StorageKey::new_storage_key(&address, b"a").to_key_bytes(),
+                ::rlp::encode(&U256::from(b'a')).into_boxed_slice(),
+            )]
+        );
+        // Reading doesn't delete.
+        assert_eq!(state_db.iter_prefix(prefix).unwrap().len(), 1);
+        assert_eq!(
+            state_db
+                .iter_prefix(StorageKey::new_storage_root_key(
+                    &Address::from_low_u64_be(3)
+                ))
+                .unwrap()
+                .len(),
+            0
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_readonly_snapshot() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut address = Address::from_low_u64_be(1);
+        address.set_user_account_type_bits();
+        let key = StorageKey::new_storage_key(&address, b"a");
+        state_db.set::<U256>(key, &U256::from(1), None).unwrap();
+        assert!(state_db.readonly_snapshot().is_err());
+
+        let account = Account::new_empty_with_balance(
+            &address,
+            &U256::from(10),
+            &U256::zero(),
+        );
+        state_db
+            .set::<Account>(
+                StorageKey::new_account_key(&address),
+                &account,
+                None,
+            )
+            .unwrap();
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+
+        let snapshot = state_db.readonly_snapshot().unwrap();
+        state_db.set::<U256>(key, &U256::from(2), None).unwrap();
+        assert_eq!(snapshot.get::<U256>(key).unwrap(), Some(U256::from(1)));
+        assert_eq!(
+            snapshot.get_raw(key).unwrap(),
+            Some(::rlp::encode(&U256::from(1)).into_boxed_slice())
+        );
+        assert_eq!(snapshot.get_account(&address).unwrap(), Some(account));
+        assert_eq!(state_db.get::<U256>(key).unwrap(), Some(U256::from(2)));
+        state_db.commit(H256::from_low_u64_be(2)).unwrap();
+    }
+
+    #[test]
+    fn test_epoch_change_summary() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        assert_eq!(
+            state_db.epoch_change_summary(),
+            EpochChangeSummary::default()
+        );
+
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        state_db
+            .set_raw(
+                StorageKey::new_storage_key(&address, b"a"),
+                vec![1u8, 2, 3].into_boxed_slice(),
+                None,
+            )
+            .unwrap();
+        state_db
+            .set_raw(
+                StorageKey::new_storage_key(&address, b"b"),
+                vec![4u8].into_boxed_slice(),
+                None,
+            )
+            .unwrap();
+        state_db
+            .delete(StorageKey::new_storage_key(&other, b"a"), None)
+            .unwrap();
+
+        let mut queue = SlotTxQueue::new();
+        let slot_info = new_test_slot_info(&address, b"slot");
+        let slot_tx = SlotTx::new(&Slot::new(&slot_info), &5, &vec![]);
+        queue.enqueue(slot_tx.clone());
+        queue.enqueue(slot_tx.clone());
+        state_db.set_global_slot_tx_queue(5, &queue, None).unwrap();
+        queue.enqueue(slot_tx);
+        state_db.set_global_slot_tx_queue(5, &queue, None).unwrap();
+
+        let summary = state_db.epoch_change_summary();
+        // The two addresses and the global slot tx queue address.
+        assert_eq!(summary.accounts_touched, 3);
+        assert_eq!(summary.slot_txs_enqueued, 3);
+        assert!(summary.bytes_written > 4);
+
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+        assert_eq!(
+            state_db.epoch_change_summary(),
+            EpochChangeSummary::default()
+        );
+    }
+
+    #[test]
+    fn test_commit_with_changeset() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let key = |k: &'static [u8]| StorageKey::new_storage_key(&address, k);
+        // The keys aren't tracked until it's enabled.
+        state_db.set_raw(key(b"c"), Box::new([1u8]), None).unwrap();
+        let (_, changed_keys) = state_db
+            .commit_with_changeset(H256::from_low_u64_be(1))
+            .unwrap();
+        assert!(changed_keys.is_empty());
+
+        state_db.enable_changeset_tracking();
+        state_db.set_raw(key(b"a"), Box::new([1u8]), None).unwrap();
+        state_db.set_raw(key(b"b"), Box::new([2u8]), None).unwrap();
+        state_db
+            .set_raw(
+                StorageKey::new_storage_key(&other, b"a"),
+                Box::new([3u8]),
+                None,
+            )
+            .unwrap();
+        let (_, changed_keys) = state_db
+            .commit_with_changeset(H256::from_low_u64_be(2))
+            .unwrap();
+        let mut expected = vec![
+            key(b"a").to_key_bytes(),
+            key(b"b").to_key_bytes(),
+            StorageKey::new_storage_key(&other, b"a").to_key_bytes(),
+        ];
+        expected.sort();
+        assert_eq!(changed_keys, expected);
+
+        // delete_all reports the deleted keys rather than the prefix.
+        state_db.delete(key(b"a"), None).unwrap();
+        state_db
+            .delete_all(StorageKey::new_storage_root_key(&other), None)
+            .unwrap();
+        let (_, changed_keys) = state_db
+            .commit_with_changeset(H256::from_low_u64_be(3))
+            .unwrap();
+        let mut expected = vec![
+            key(b"a").to_key_bytes(),
+            StorageKey::new_storage_key(&other, b"a").to_key_bytes(),
+        ];
+        expected.sort();
+        assert_eq!(changed_keys, expected);
+
+        let (_, changed_keys) = state_db
+            .commit_with_changeset(H256::from_low_u64_be(4))
+            .unwrap();
+        assert!(changed_keys.is_empty());
+    }
+
+    #[test]
+    fn test_metrics() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let key = |k: &'static [u8]| StorageKey::new_storage_key(&address, k);
+        state_db.set_raw(key(b"a"), Box::new([1u8]), None).unwrap();
+        assert_eq!(state_db.metrics(), None);
+
+        state_db.enable_metrics();
+        state_db.set_raw(key(b"a"), Box::new([1u8, 2, 3]), None).unwrap();
+        state_db.set_raw(key(b"b"), Box::new([4u8]), None).unwrap();
+        state_db.delete(key(b"b"), None).unwrap();
+        state_db
+            .delete_all(StorageKey::new_storage_root_key(&address), None)
+            .unwrap();
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+        // Enabling again keeps the counters, and commit doesn't reset them.
+        state_db.enable_metrics();
+        assert_eq!(
+            state_db.metrics(),
+            Some(StateDbMetrics {
+                writes: 2,
+                deletes: 2,
+                bytes_written: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_if() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let key = StorageKey::new_storage_key(&address, b"a");
+        let (one, two) = (U256::from(1), U256::from(2));
+
+        assert!(!state_db.set_if(key, Some(&one), &two, None).unwrap());
+        assert_eq!(state_db.get::<U256>(key).unwrap(), None);
+        assert!(state_db.set_if(key, None, &one, None).unwrap());
+        assert_eq!(state_db.get::<U256>(key).unwrap(), Some(one));
+
+        let mut debug_record = ComputeEpochDebugRecord::default();
+        assert!(!state_db
+            .set_if(key, None, &two, Some(&mut debug_record))
+            .unwrap());
+        assert!(!state_db
+            .set_if(key, Some(&two), &two, Some(&mut debug_record))
+            .unwrap());
+        assert!(debug_record.state_ops.is_empty());
+        assert!(state_db
+            .set_if(key, Some(&one), &two, Some(&mut debug_record))
+            .unwrap());
+        assert_eq!(debug_record.state_ops.len(), 1);
+        assert_eq!(state_db.get::<U256>(key).unwrap(), Some(two));
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_account_with_proof() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut address = Address::from_low_u64_be(1);
+        address.set_user_account_type_bits();
+        let key = StorageKey::new_account_key(&address);
+        assert_eq!(state_db.get_account_with_proof(&address).unwrap().0, None);
+
+        let account = Account::new_empty_with_balance(
+            &address,
+            &U256::from(10),
+            &U256::zero(),
+        );
+        state_db.set::<Account>(key, &account, None).unwrap();
+        let (maybe_account, proof) =
+            state_db.get_account_with_proof(&address).unwrap();
+        assert_eq!(maybe_account, state_db.get_account(&address).unwrap());
+        assert_eq!(maybe_account, Some(account));
+        assert_eq!(proof, state_db.get_raw_with_proof(key).unwrap().1);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_decode_error_has_key() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let key = StorageKey::new_account_key(&address);
+        let bad = vec![0xc1u8, 0x01u8].into_boxed_slice();
+        state_db.set_raw(key, bad, None).unwrap();
+        match state_db.get_account(&address).unwrap_err().kind() {
+            ErrorKind::DecodeError(error_key, _) => {
+                assert_eq!(*error_key, key.to_key_bytes());
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_cached_state_db() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut address = Address::from_low_u64_be(1);
+        address.set_user_account_type_bits();
+        let mut other = Address::from_low_u64_be(2);
+        other.set_user_account_type_bits();
+        let account = Account::new_empty_with_balance(
+            &address,
+            &U256::from(10),
+            &U256::zero(),
+        );
+        state_db
+            .set::<Account>(
+                StorageKey::new_account_key(&address),
+                &account,
+                None,
+            )
+            .unwrap();
+
+        let mut cached = CachedStateDb::new(state_db, 1);
+        for _ in 0..2 {
+            assert_eq!(
+                cached.get_account(&address).unwrap(),
+                Some(account.clone())
+            );
+        }
+        assert_eq!(cached.stats(), CacheStats { hits: 1, misses: 1 });
+
+        // A write under the address drops the cached account.
+        let updated = Account::new_empty_with_balance(
+            &address,
+            &U256::from(20),
+            &U256::zero(),
+        );
+        cached
+            .set::<Account>(
+                StorageKey::new_account_key(&address),
+                &updated,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            cached.get_account(&address).unwrap(),
+            Some(updated.clone())
+        );
+        assert_eq!(cached.stats(), CacheStats { hits: 1, misses: 2 });
+
+        // The cache holds one account, so reading another evicts it.
+        assert_eq!(cached.get_account(&other).unwrap(), None);
+        assert_eq!(cached.get_account(&other).unwrap(), None);
+        assert_eq!(cached.get_account(&address).unwrap(), Some(updated));
+        assert_eq!(cached.stats(), CacheStats { hits: 2, misses: 4 });
+
+        let mut state_db = cached.into_inner();
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_many() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let keys: Vec<StorageKey> = [&b"a"[..], &b"b"[..], &b"c"[..]]
+            .iter()
+            .map(|key| StorageKey::new_storage_key(&address, key))
+            .collect();
+        state_db.set::<U256>(keys[0], &U256::from(1), None).unwrap();
+        state_db.set::<U256>(keys[2], &U256::from(3), None).unwrap();
+        assert_eq!(
+            state_db.get_many::<U256>(&keys).unwrap(),
+            vec![Some(U256::from(1)), None, Some(U256::from(3))]
+        );
+
+        let bad = vec![0xc1u8, 0x01u8].into_boxed_slice();
+        state_db.set_raw(keys[1], bad, None).unwrap();
+        match state_db.get_many::<U256>(&keys).unwrap_err().kind() {
+            ErrorKind::DecodeManyError(index, key, _) => {
+                assert_eq!(*index, 1);
+                assert_eq!(*key, keys[1].to_key_bytes());
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_raw_reads_uncommitted_writes() {
+        let address = Address::from_low_u64_be(1);
+        let key = StorageKey::new_storage_key(&address, b"key");
+
+        let (_storage_manager, mut state_db) = new_state_db();
+        state_db
+            .set_raw(key, vec![1u8].into_boxed_slice(), None)
+            .unwrap();
+        assert_eq!(state_db.get_raw(key).unwrap(), Some(vec![1u8].into()));
+        state_db
+            .set_raw(key, vec![2u8].into_boxed_slice(), None)
+            .unwrap();
+        assert_eq!(state_db.get_raw(key).unwrap(), Some(vec![2u8].into()));
+        state_db.delete(key, None).unwrap();
+        assert_eq!(state_db.get_raw(key).unwrap(), None);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_storage_range() {
+        let address = Address::from_low_u64_be(1);
+        let (_storage_manager, mut state_db) = new_state_db();
+        state_db
+            .set_storage_layout(&address, &StorageLayout::Regular(0), None)
+            .unwrap();
+        for key in &[b"a", b"b", b"c", b"d"] {
+            state_db
+                .set_raw(
+                    StorageKey::new_storage_key(&address, *key),
+                    key.to_vec().into_boxed_slice(),
+                    None,
+                )
+                .unwrap();
+        }
+
+        let keys = |range: Vec<(Vec<u8>, Box<[u8]>)>| {
+            range.into_iter().map(|(key, _)| key).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(state_db.get_storage_range(&address, b"", b"z", 10).unwrap()),
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+        assert_eq!(
+            keys(state_db.get_storage_range(&address, b"b", b"d", 10).unwrap()),
+            vec![b"b".to_vec(), b"c".to_vec()]
+        );
+        assert_eq!(
+            keys(state_db.get_storage_range(&address, b"a", b"z", 1).unwrap()),
+            vec![b"a".to_vec()]
+        );
+        // The storage layout doesn't count towards the limit.
+        assert_eq!(
+            keys(state_db.get_storage_range(&address, b"", b"z", 1).unwrap()),
+            vec![b"a".to_vec()]
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_account_storage() {
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let (_storage_manager, mut state_db) = new_state_db();
+        assert!(state_db.get_account_storage(&address).unwrap().is_empty());
+
+        state_db
+            .set_storage_layout(&address, &StorageLayout::Regular(0), None)
+            .unwrap();
+        state_db
+            .set_raw(
+                StorageKey::new_account_key(&address),
+                Box::new([1u8]),
+                None,
+            )
+            .unwrap();
+        state_db
+            .set_raw(
+                StorageKey::new_code_key(&address, &H256::zero()),
+                Box::new([2u8]),
+                None,
+            )
+            .unwrap();
+        for key in &[&b"b"[..], &[0xffu8][..], &b"a"[..]] {
+            state_db
+                .set_raw(
+                    StorageKey::new_storage_key(&address, key),
+                    key.to_vec().into_boxed_slice(),
+                    None,
+                )
+                .unwrap();
+        }
+        state_db
+            .set_raw(
+                StorageKey::new_storage_key(&other, b"c"),
+                Box::new([3u8]),
+                None,
+            )
+            .unwrap();
+
+        let expected: Vec<(Vec<u8>, Box<[u8]>)> =
+            vec![b"a".to_vec(), b"b".to_vec(), vec![0xffu8]]
+                .into_iter()
+                .map(|key| (key.clone(), key.into_boxed_slice()))
+                .collect();
+        assert_eq!(state_db.get_account_storage(&address).unwrap(), expected);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_compute_total_storage_collateral() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        for (address, key) in &[(1, b"a"), (1, b"b"), (2, b"a")] {
+            let address = Address::from_low_u64_be(*address);
+            state_db
+                .set_storage_layout(&address, &StorageLayout::Regular(0), None)
+                .unwrap();
+            state_db
+                .set_raw(
+                    StorageKey::new_storage_key(&address, *key),
+                    vec![1u8].into_boxed_slice(),
+                    None,
+                )
+                .unwrap();
+        }
+        state_db
+            .set_total_storage_tokens(&U256::from(1000), None)
+            .unwrap();
+
+        assert_eq!(
+            state_db.compute_total_storage_collateral().unwrap(),
+            *COLLATERAL_PER_STORAGE_KEY * 3
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_account_balance_and_collateral() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut address = Address::from_low_u64_be(1);
+        address.set_user_account_type_bits();
+        assert_eq!(
+            state_db.get_account_balance_and_collateral(&address).unwrap(),
+            None
+        );
+
+        let mut account = Account::new_empty_with_balance(
+            &address,
+            &U256::from(1000),
+            &U256::zero(),
+        );
+        account.collateral_for_storage = U256::from(64);
+        let key = StorageKey::new_account_key(&address);
+        state_db.set::<Account>(key, &account, None).unwrap();
+        assert_eq!(
+            state_db.get_account_balance_and_collateral(&address).unwrap(),
+            Some((U256::from(1000), U256::from(64)))
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_total_locked_stake() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        assert_eq!(state_db.total_locked_stake(&address).unwrap(), 0.into());
+
+        let deposit = |amount: U256| DepositInfo {
+            amount,
+            deposit_time: 1,
+            accumulated_interest_rate: U256::from(10),
+        };
+        let deposit_list =
+            DepositList(vec![deposit(1000.into()), deposit(2000.into())]);
+        let key = StorageKey::new_deposit_list_key(&address);
+        state_db.set::<DepositList>(key, &deposit_list, None).unwrap();
+        let vote_stake_list = VoteStakeList(vec![VoteStakeInfo {
+            amount: U256::from(2500),
+            unlock_block_number: 10,
+        }]);
+        let key = StorageKey::new_vote_list_key(&address);
+        state_db.set::<VoteStakeList>(key, &vote_stake_list, None).unwrap();
+        assert_eq!(
+            state_db.total_locked_stake(&address).unwrap(),
+            3000.into()
+        );
+
+        let deposit_list =
+            DepositList(vec![deposit(U256::max_value()), deposit(1.into())]);
+        let key = StorageKey::new_deposit_list_key(&address);
+        state_db.set::<DepositList>(key, &deposit_list, None).unwrap();
+        assert_eq!(
+            state_db.total_locked_stake(&address).unwrap(),
+            U256::max_value()
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_iter_deposit_lists() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let deposit_list = DepositList(vec![DepositInfo {
+            amount: U256::from(1000),
+            deposit_time: 1,
+            accumulated_interest_rate: U256::from(10),
+        }]);
+        for address in &[3, 2] {
+            let address = Address::from_low_u64_be(*address);
+            state_db
+                .set::<DepositList>(
+                    StorageKey::new_deposit_list_key(&address),
+                    &deposit_list,
+                    None,
+                )
+                .unwrap();
+        }
+        let broken = Address::from_low_u64_be(4);
+        state_db
+            .set_raw(
+                StorageKey::new_deposit_list_key(&broken),
+                vec![0xc1u8, 0x01u8].into_boxed_slice(),
+                None,
+            )
+            .unwrap();
+        state_db
+            .set::<VoteStakeList>(
+                StorageKey::new_vote_list_key(&Address::from_low_u64_be(5)),
+                &VoteStakeList::default(),
+                None,
+            )
+            .unwrap();
+
+        let items = state_db.iter_deposit_lists().unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[0].as_ref().unwrap(),
+            &(Address::from_low_u64_be(2), deposit_list.clone())
+        );
+        assert_eq!(
+            items[1].as_ref().unwrap(),
+            &(Address::from_low_u64_be(3), deposit_list)
+        );
+        assert!(items[2].is_err());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_verify_total_staking_tokens() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        assert_eq!(state_db.verify_total_staking_tokens().unwrap(), None);
+
+        for (address, amount) in &[(1, 1000), (2, 500)] {
+            let deposit_list = DepositList(vec![DepositInfo {
+                amount: U256::from(*amount),
+                deposit_time: 1,
+                accumulated_interest_rate: U256::from(10),
+            }]);
+            state_db
+                .set::<DepositList>(
+                    StorageKey::new_deposit_list_key(
+                        &Address::from_low_u64_be(*address),
+                    ),
+                    &deposit_list,
+                    None,
+                )
+                .unwrap();
+        }
+        assert_eq!(
+            state_db.verify_total_staking_tokens().unwrap(),
+            Some(StakingDrift {
+                recorded: U256::zero(),
+                deposited: U512::from(1500),
+            })
+        );
+        state_db
+            .set_total_staking_tokens(&U256::from(1500), None)
+            .unwrap();
+        assert_eq!(state_db.verify_total_staking_tokens().unwrap(), None);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_interest_rates_raw() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        assert_eq!(state_db.get_interest_rates_raw().unwrap(), (None, None));
+
+        // Storing the default value is distinguishable from never storing.
+        let default_rate = state_db.get_annual_interest_rate().unwrap();
+        state_db.set_annual_interest_rate(&default_rate, None).unwrap();
+        assert_eq!(
+            state_db.get_interest_rates_raw().unwrap(),
+            (Some(default_rate), None)
+        );
+        state_db.set_accumulate_interest_rate(&U256::from(7), None).unwrap();
+        assert_eq!(
+            state_db.get_interest_rates_raw().unwrap(),
+            (Some(default_rate), Some(U256::from(7)))
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_account_exists() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut address = Address::from_low_u64_be(1);
+        address.set_user_account_type_bits();
+        assert!(!state_db.account_exists(&address).unwrap());
+
+        let account = Account::new_empty_with_balance(
+            &address,
+            &U256::from(10),
+            &U256::zero(),
+        );
+        let key = StorageKey::new_account_key(&address);
+        state_db.set::<Account>(key, &account, None).unwrap();
+        assert!(state_db.account_exists(&address).unwrap());
+
+        let empty = Address::from_low_u64_be(2);
+        state_db
+            .set_raw(StorageKey::new_account_key(&empty), Box::new([]), None)
+            .unwrap();
+        assert!(!state_db.account_exists(&empty).unwrap());
+
+        state_db.delete(key, None).unwrap();
+        assert!(!state_db.account_exists(&address).unwrap());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_staking_lists() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let deposit_list = DepositList(vec![DepositInfo {
+            amount: U256::from(1000),
+            deposit_time: 1,
+            accumulated_interest_rate: U256::from(1),
+        }]);
+        let vote_list = VoteStakeList(vec![VoteStakeInfo {
+            amount: U256::from(500),
+            unlock_block_number: 10,
+        }]);
+        let both = Address::from_low_u64_be(1);
+        let votes_only = Address::from_low_u64_be(2);
+        state_db
+            .set::<DepositList>(
+                StorageKey::new_deposit_list_key(&both),
+                &deposit_list,
+                None,
+            )
+            .unwrap();
+        for address in &[both, votes_only] {
+            state_db
+                .set::<VoteStakeList>(
+                    StorageKey::new_vote_list_key(address),
+                    &vote_list,
+                    None,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            state_db.get_staking_lists(&both).unwrap(),
+            (Some(deposit_list), Some(vote_list.clone()))
+        );
+        assert_eq!(
+            state_db.get_staking_lists(&votes_only).unwrap(),
+            (None, Some(vote_list))
+        );
+        assert_eq!(
+            state_db
+                .get_staking_lists(&Address::from_low_u64_be(3))
+                .unwrap(),
+            (None, None)
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_iter_vote_lists() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let vote_list = VoteStakeList(vec![VoteStakeInfo {
+            amount: U256::from(1000),
+            unlock_block_number: 10,
+        }]);
+        for address in &[3, 2] {
+            let address = Address::from_low_u64_be(*address);
+            state_db
+                .set::<VoteStakeList>(
+                    StorageKey::new_vote_list_key(&address),
+                    &vote_list,
+                    None,
+                )
+                .unwrap();
+        }
+        let broken = Address::from_low_u64_be(4);
+        state_db
+            .set_raw(
+                StorageKey::new_vote_list_key(&broken),
+                vec![0xc1u8, 0x01u8].into_boxed_slice(),
+                None,
+            )
+            .unwrap();
+        state_db
+            .set::<DepositList>(
+                StorageKey::new_deposit_list_key(&Address::from_low_u64_be(5)),
+                &DepositList::default(),
+                None,
+            )
+            .unwrap();
+
+        let items = state_db.iter_vote_lists().unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[0].as_ref().unwrap(),
+            &(Address::from_low_u64_be(2), vote_list.clone())
+        );
+        assert_eq!(
+            items[1].as_ref().unwrap(),
+            &(Address::from_low_u64_be(3), vote_list)
+        );
+        assert!(items[2].is_err());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_is_contract() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut user = Address::from_low_u64_be(1);
+        user.set_user_account_type_bits();
+        let mut contract = Address::from_low_u64_be(2);
+        contract.set_contract_type_bits();
+        assert!(!state_db.is_contract(&contract).unwrap());
+
+        for address in &[user, contract] {
+            let account = Account::new_empty_with_balance(
+                address,
+                &U256::zero(),
+                &U256::zero(),
+            );
+            let key = StorageKey::new_account_key(address);
+            state_db.set::<Account>(key, &account, None).unwrap();
+        }
+        assert!(!state_db.is_contract(&user).unwrap());
+        assert!(!state_db.is_contract(&contract).unwrap());
+
+        let mut account = state_db.get_account(&contract).unwrap().unwrap();
+        account.code_hash = keccak(&[0x60u8, 0x00u8]);
+        let key = StorageKey::new_account_key(&contract);
+        state_db.set::<Account>(key, &account, None).unwrap();
+        assert!(state_db.is_contract(&contract).unwrap());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_create_contract() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut address = Address::from_low_u64_be(1);
+        address.set_contract_type_bits();
+        let code = CodeInfo {
+            code: vec![0x60u8, 0x00u8],
+            owner: address,
+        };
+        let layout = StorageLayout::Regular(0);
+        let mut account = Account::new_empty_with_balance(
+            &address,
+            &U256::zero(),
+            &U256::zero(),
+        );
+
+        let mut debug_record = ComputeEpochDebugRecord::default();
+        match state_db
+            .create_contract(
+                &address,
+                &account,
+                &code,
+                &layout,
+                Some(&mut debug_record),
+            )
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::CodeHashMismatch(_, expected, got) => {
+                assert_eq!(*expected, KECCAK_EMPTY);
+                assert_eq!(*got, keccak(&code.code));
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(debug_record.state_ops.is_empty());
+        assert!(state_db.get_account(&address).unwrap().is_none());
+
+        account.code_hash = keccak(&code.code);
+        state_db
+            .create_contract(
+                &address,
+                &account,
+                &code,
+                &layout,
+                Some(&mut debug_record),
+            )
+            .unwrap();
+        assert_eq!(debug_record.state_ops.len(), 3);
+        assert!(state_db.is_contract(&address).unwrap());
+        assert!(state_db.verify_account_code_hash(&address).unwrap());
+        assert_eq!(
+            state_db.get_code(&address, &account.code_hash).unwrap(),
+            Some(code)
+        );
+        let key = StorageKey::new_storage_root_key(&address);
+        assert_eq!(
+            state_db.get_raw(key).unwrap().map(|raw| raw.to_vec()),
+            Some(layout.to_bytes())
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_repair_account_code_hash() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let mut address = Address::from_low_u64_be(1);
+        address.set_contract_type_bits();
+        assert!(state_db.verify_account_code_hash(&address).unwrap());
+
+        let code = CodeInfo {
+            code: vec![0x60u8, 0x00u8],
+            owner: address,
+        };
+        let wrong_hash = H256::from_low_u64_be(9);
+        let mut account = Account::new_empty_with_balance(
+            &address,
+            &U256::zero(),
+            &U256::zero(),
+        );
+        account.code_hash = wrong_hash;
+        let key = StorageKey::new_account_key(&address);
+        state_db.set::<Account>(key, &account, None).unwrap();
+        let key = StorageKey::new_code_key(&address, &wrong_hash);
+        state_db.set::<CodeInfo>(key, &code, None).unwrap();
+
+        assert!(!state_db.verify_account_code_hash(&address).unwrap());
+        assert!(state_db.repair_account_code_hash(&address, None).unwrap());
+        assert!(state_db.verify_account_code_hash(&address).unwrap());
+        assert!(!state_db.repair_account_code_hash(&address, None).unwrap());
+
+        let code_hash = keccak(&code.code);
+        let account = state_db.get_account(&address).unwrap().unwrap();
+        assert_eq!(account.code_hash, code_hash);
+        assert_eq!(
+            state_db.get_code(&address, &code_hash).unwrap(),
+            Some(code)
+        );
+        assert_eq!(state_db.get_code(&address, &wrong_hash).unwrap(), None);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_transfer_balance() {
+        let mut from = Address::from_low_u64_be(1);
+        from.set_user_account_type_bits();
+        let mut to = Address::from_low_u64_be(2);
+        to.set_user_account_type_bits();
+
+        let (_storage_manager, mut state_db) = new_state_db();
+        state_db
+            .set::<Account>(
+                StorageKey::new_account_key(&from),
+                &Account::new_empty_with_balance(
+                    &from,
+                    &U256::from(10),
+                    &U256::zero(),
+                ),
+                None,
+            )
+            .unwrap();
+        let balance = |state_db: &StateDb, address| {
+            state_db
+                .get_account(address)
+                .unwrap()
+                .map(|account| account.balance)
+        };
+
+        state_db
+            .transfer_balance(&from, &to, &U256::from(4), None)
+            .unwrap();
+        assert_eq!(balance(&state_db, &from), Some(U256::from(6)));
+        assert_eq!(balance(&state_db, &to), Some(U256::from(4)));
+
+        match state_db
+            .transfer_balance(&from, &to, &U256::from(7), None)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::InsufficientBalance(address, amount, balance) => {
+                assert_eq!(*address, from);
+                assert_eq!(*amount, U256::from(7));
+                assert_eq!(*balance, U256::from(6));
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        assert_eq!(balance(&state_db, &from), Some(U256::from(6)));
+        assert_eq!(balance(&state_db, &to), Some(U256::from(4)));
+        assert!(state_db
+            .transfer_balance(&from, &from, &U256::from(7), None)
+            .is_err());
+        assert_eq!(balance(&state_db, &from), Some(U256::from(6)));
+        assert_eq!(balance(&state_db, &to), Some(U256::from(4)));
+
+        state_db
+            .transfer_balance(&from, &from, &U256::from(6), None)
+            .unwrap();
+        assert_eq!(balance(&state_db, &from), Some(U256::from(6)));
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_scan_accounts_with_slot_tx_queue() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = new_test_slot_info(&owner, b"slot");
+        let mut queue = SlotTxQueue::new();
+        queue.enqueue(SlotTx::new(&Slot::new(&slot_info), &1, &vec![]));
+        for address in &[3, 2] {
+            state_db
+                .set_account_slot_tx_queue(
+                    &Address::from_low_u64_be(*address),
+                    &queue,
+                    None,
+                )
+                .unwrap();
+        }
+        state_db
+            .set_account_slot_tx_queue(
+                &Address::from_low_u64_be(4),
+                &SlotTxQueue::new(),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            state_db.scan_accounts_with_slot_tx_queue().unwrap(),
+            vec![Address::from_low_u64_be(2), Address::from_low_u64_be(3)]
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_raw_len_and_slot_tx_queue_len() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let address = Address::from_low_u64_be(2);
+        let key = StorageKey::new_slot_tx_queue_key(&address);
+        assert_eq!(state_db.get_raw_len(key).unwrap(), None);
+        assert_eq!(
+            state_db.get_account_slot_tx_queue_len(&address).unwrap(),
+            None
+        );
+
+        let slot_info = new_test_slot_info(&owner, b"slot");
+        let mut queue = SlotTxQueue::new();
+        for epoch_height in 1..4u64 {
+            queue.enqueue(SlotTx::new(
+                &Slot::new(&slot_info),
+                &epoch_height,
+                &vec![],
+            ));
+        }
+        state_db.set_account_slot_tx_queue(&address, &queue, None).unwrap();
+        assert_eq!(
+            state_db.get_raw_len(key).unwrap(),
+            Some(::rlp::encode(&queue).len())
+        );
+        assert_eq!(
+            state_db.get_account_slot_tx_queue_len(&address).unwrap(),
+            Some(3)
+        );
+
+        state_db.set_raw(key, Box::new([0x01u8]), None).unwrap();
+        assert_eq!(state_db.get_raw_len(key).unwrap(), Some(1));
+        assert!(state_db.get_account_slot_tx_queue_len(&address).is_err());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_verify_ready_list_invariant() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        assert!(state_db.verify_ready_list_invariant().unwrap().is_empty());
+
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = new_test_slot_info(&owner, b"slot");
+        let mut queue = SlotTxQueue::new();
+        queue.enqueue(SlotTx::new(&Slot::new(&slot_info), &1, &vec![]));
+        state_db
+            .set_account_slot_tx_queue(
+                &Address::from_low_u64_be(2),
+                &queue,
+                None,
+            )
+            .unwrap();
+        state_db
+            .set_account_slot_tx_queue(
+                &Address::from_low_u64_be(3),
+                &SlotTxQueue::new(),
+                None,
+            )
+            .unwrap();
+        let mut ready_list = SlotTxAddressList::new();
+        for address in &[2, 3, 4] {
+            ready_list.add(&Address::from_low_u64_be(*address));
+        }
+        state_db
+            .set_addresses_with_ready_slot_tx(&ready_list, None)
+            .unwrap();
+
+        assert_eq!(
+            state_db.verify_ready_list_invariant().unwrap(),
+            vec![Address::from_low_u64_be(3), Address::from_low_u64_be(4)]
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_add_remove_ready_slot_tx_address() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let first = Address::from_low_u64_be(1);
+        let second = Address::from_low_u64_be(2);
+        for address in &[first, second, first] {
+            state_db.add_ready_slot_tx_address(address, None).unwrap();
+        }
+        let ready_list = state_db.get_addresses_with_ready_slot_tx().unwrap();
+        assert_eq!(ready_list.unwrap().get_all(), vec![first, second]);
+
+        // Removing an address which isn't in the list is a no-op.
+        state_db
+            .remove_ready_slot_tx_address(&Address::from_low_u64_be(3), None)
+            .unwrap();
+        state_db.remove_ready_slot_tx_address(&first, None).unwrap();
+        let ready_list = state_db.get_addresses_with_ready_slot_tx().unwrap();
+        assert_eq!(ready_list.unwrap().get_all(), vec![second]);
+
+        state_db.remove_ready_slot_tx_address(&second, None).unwrap();
+        assert_eq!(state_db.get_addresses_with_ready_slot_tx().unwrap(), None);
+        state_db.remove_ready_slot_tx_address(&second, None).unwrap();
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_set_slot_infos() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let contract = Address::from_low_u64_be(1);
+        let slots: Vec<(Vec<u8>, SlotInfo)> = [&b"b"[..], &b"a"[..]]
+            .iter()
+            .map(|slot_key| {
+                let slot_info = new_test_slot_info(&contract, slot_key);
+                (slot_key.to_vec(), slot_info)
+            })
+            .collect();
+        let mut debug_record = ComputeEpochDebugRecord::default();
+        state_db
+            .set_slot_infos(&contract, &slots, Some(&mut debug_record))
+            .unwrap();
+
+        for (slot_key, slot_info) in &slots {
+            assert_eq!(
+                state_db.get_slot_info(&contract, slot_key).unwrap().as_ref(),
+                Some(slot_info)
+            );
+        }
+        let expected_keys: Vec<Vec<u8>> = slots
+            .iter()
+            .map(|(slot_key, _)| {
+                StorageKey::new_slot_key(&contract, slot_key).to_key_bytes()
+            })
+            .collect();
+        let recorded_keys: Vec<Vec<u8>> = debug_record
+            .state_ops
+            .iter()
+            .map(|op| match op {
+                StateOp::StorageLevelOp { key, .. } => key.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(recorded_keys, expected_keys);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_find_oversized_signals() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let contract = Address::from_low_u64_be(1);
+        for (signal_key, slot_count) in &[(b"a", 1), (b"b", 3), (b"c", 2)] {
+            let signal_key = signal_key.to_vec();
+            let mut sig_info = SignalInfo::new(&contract, &signal_key);
+            for i in 0..*slot_count {
+                sig_info
+                    .add_to_slot_list(&new_test_slot_info(&contract, &[i]))
+                    .unwrap();
+            }
+            state_db
+                .set_signal_info(&contract, &signal_key, &sig_info, None)
+                .unwrap();
+        }
+
+        let mut oversized =
+            state_db.find_oversized_signals(&contract, 1).unwrap();
+        oversized.sort();
+        assert_eq!(oversized, vec![(b"b".to_vec(), 3), (b"c".to_vec(), 2)]);
+        assert!(state_db
+            .find_oversized_signals(&contract, 3)
+            .unwrap()
+            .is_empty());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_signal_slot_fingerprint() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let contract = Address::from_low_u64_be(1);
+        let slot_infos: Vec<SlotInfo> = [&b"a"[..], &b"b"[..]]
+            .iter()
+            .map(|slot_key| new_test_slot_info(&contract, slot_key))
+            .collect();
+        let signal_key = b"signal".to_vec();
+        let empty = state_db.signal_slot_fingerprint(&contract).unwrap();
+
+        // Bind both slots to the signal in either order, writing the
+        // SlotInfos in either order too.
+        let mut fingerprints = Vec::new();
+        for order in &[[0, 1], [1, 0]] {
+            let mut sig_info = SignalInfo::new(&contract, &signal_key);
+            for i in order {
+                sig_info.add_to_slot_list(&slot_infos[*i]).unwrap();
+            }
+            state_db
+                .set_signal_info(&contract, &signal_key, &sig_info, None)
+                .unwrap();
+            for i in order {
+                let mut slot_info = slot_infos[*i].clone();
+                slot_info.add_to_bind_list(sig_info.location());
+                let slot_key = slot_info.location().slot_key().clone();
+                state_db
+                    .set_slot_info(&contract, &slot_key, &slot_info, None)
+                    .unwrap();
+            }
+            fingerprints
+                .push(state_db.signal_slot_fingerprint(&contract).unwrap());
+        }
+        assert_eq!(fingerprints[0], fingerprints[1]);
+        assert_ne!(fingerprints[0], empty);
+
+        state_db.delete_slot_info(&contract, &b"b".to_vec(), None).unwrap();
+        assert_ne!(
+            state_db.signal_slot_fingerprint(&contract).unwrap(),
+            fingerprints[0]
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_find_duplicate_slot_txs() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let contract = Address::from_low_u64_be(2);
+        let slot = Slot::new(&new_test_slot_info(&owner, b"slot"));
+        let other_slot = Slot::new(&new_test_slot_info(&owner, b"other"));
+        assert!(state_db
+            .find_duplicate_slot_txs(&contract)
+            .unwrap()
+            .is_empty());
+
+        let mut queue = SlotTxQueue::new();
+        queue.enqueue(SlotTx::new(&slot, &1, &vec![0x01u8]));
+        queue.enqueue(SlotTx::new(&slot, &2, &vec![0x01u8]));
+        queue.enqueue(SlotTx::new(&slot, &1, &vec![0x02u8]));
+        queue.enqueue(SlotTx::new(&other_slot, &1, &vec![0x01u8]));
+        state_db
+            .set_account_slot_tx_queue(&contract, &queue, None)
+            .unwrap();
+        assert!(state_db
+            .find_duplicate_slot_txs(&contract)
+            .unwrap()
+            .is_empty());
+
+        queue.enqueue(SlotTx::new(&slot, &2, &vec![0x01u8]));
+        queue.enqueue(SlotTx::new(&slot, &1, &vec![0x01u8]));
+        queue.enqueue(SlotTx::new(&slot, &1, &vec![0x01u8]));
+        state_db
+            .set_account_slot_tx_queue(&contract, &queue, None)
+            .unwrap();
+        assert_eq!(
+            state_db.find_duplicate_slot_txs(&contract).unwrap(),
+            vec![(1, 4), (0, 5), (0, 6)]
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_dedup_account_slot_tx_queue() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let contract = Address::from_low_u64_be(2);
+        let slot = Slot::new(&new_test_slot_info(&owner, b"slot"));
+        assert_eq!(
+            state_db.dedup_account_slot_tx_queue(&contract, None).unwrap(),
+            0
+        );
+
+        // The gas isn't compared, so it tells which copy is kept.
+        let mut first = SlotTx::new(&slot, &1, &vec![]);
+        first.set_gas(U256::from(10));
+        let mut queue = SlotTxQueue::new();
+        queue.enqueue(first.clone());
+        for epoch_height in &[2, 1, 3, 2] {
+            queue.enqueue(SlotTx::new(&slot, epoch_height, &vec![]));
+        }
+        state_db
+            .set_account_slot_tx_queue(&contract, &queue, None)
+            .unwrap();
+
+        let mut debug_record = ComputeEpochDebugRecord::default();
+        assert_eq!(
+            state_db
+                .dedup_account_slot_tx_queue(&contract, Some(&mut debug_record))
+                .unwrap(),
+            2
+        );
+        assert_eq!(debug_record.state_ops.len(), 1);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+
+        let cleaned = state_db
+            .get_account_slot_tx_queue(&contract)
+            .unwrap()
+            .unwrap();
+        let txs: Vec<SlotTx> = cleaned.iter().cloned().collect();
+        assert_eq!(
+            txs,
+            vec![
+                first,
+                SlotTx::new(&slot, &2, &vec![]),
+                SlotTx::new(&slot, &3, &vec![]),
+            ]
+        );
+        assert_eq!(
+            state_db.dedup_account_slot_tx_queue(&contract, None).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_prune_expired_slot_txs() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let slot = Slot::new(&new_test_slot_info(&owner, b"slot"));
+        let contracts =
+            [Address::from_low_u64_be(2), Address::from_low_u64_be(3)];
+        let mut ready_list = SlotTxAddressList::new();
+        for contract in &contracts {
+            let mut queue = SlotTxQueue::new();
+            for epoch_height in &[5, 10, 20] {
+                queue.enqueue(SlotTx::new(&slot, epoch_height, &vec![]));
+            }
+            state_db
+                .set_account_slot_tx_queue(contract, &queue, None)
+                .unwrap();
+            ready_list.add(contract);
+        }
+        state_db
+            .set_addresses_with_ready_slot_tx(&ready_list, None)
+            .unwrap();
+
+        // Transactions exactly at the edge of the grace window are kept.
+        assert_eq!(
+            state_db
+                .prune_expired_slot_txs(&contracts[0], 20, 10, None)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            state_db.account_pending_epochs(&contracts[0]).unwrap(),
+            vec![10, 20]
+        );
+        assert_eq!(
+            state_db
+                .prune_expired_slot_txs(&contracts[0], 20, 10, None)
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(
+            state_db
+                .prune_expired_slot_txs(&contracts[0], 100, 10, None)
+                .unwrap(),
+            2
+        );
+        assert!(state_db
+            .get_account_slot_tx_queue(&contracts[0])
+            .unwrap()
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            state_db
+                .get_addresses_with_ready_slot_tx()
+                .unwrap()
+                .unwrap()
+                .get_list(),
+            &vec![contracts[1]]
+        );
+
+        assert_eq!(
+            state_db
+                .prune_expired_slot_txs(&contracts[1], 100, 10, None)
+                .unwrap(),
+            3
+        );
+        assert!(state_db.get_addresses_with_ready_slot_tx().unwrap().is_none());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_signal_infos() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let signal_keys = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
+        let sig_infos: Vec<SignalInfo> = signal_keys
+            .iter()
+            .map(|signal_key| SignalInfo::new(&address, signal_key))
+            .collect();
+        state_db
+            .set_signal_info(&address, &signal_keys[0], &sig_infos[0], None)
+            .unwrap();
+        state_db
+            .set_signal_info(&address, &signal_keys[2], &sig_infos[2], None)
+            .unwrap();
+        assert_eq!(
+            state_db.get_signal_infos(&address, &signal_keys).unwrap(),
+            vec![Some(sig_infos[0].clone()), None, Some(sig_infos[2].clone())]
+        );
+        assert!(state_db.get_signal_infos(&address, &[]).unwrap().is_empty());
+
+        let bad = vec![0xc1u8, 0x01u8].into_boxed_slice();
+        let bad_key = StorageKey::new_signal_key(&address, &signal_keys[1]);
+        state_db.set_raw(bad_key, bad, None).unwrap();
+        match state_db
+            .get_signal_infos(&address, &signal_keys)
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::DecodeManyError(index, key, _) => {
+                assert_eq!(*index, 1);
+                assert_eq!(*key, bad_key.to_key_bytes());
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_gc_signal_slot_state() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let live_slot = new_test_slot_info(&address, b"live");
+        let gone_slot = new_test_slot_info(&address, b"gone");
+        let mut sig_info = SignalInfo::new(&address, b"signal");
+        sig_info.add_to_slot_list(&live_slot).unwrap();
+        sig_info.add_to_slot_list(&gone_slot).unwrap();
+        let gone_signal = SignalInfo::new(&address, b"gone");
+        let other_gone_signal = SignalInfo::new(&address, b"other gone");
+
+        // The live slot is bound to the live signal and to a signal which is
+        // gone, the orphaned slot only to a signal which is gone.
+        let mut bound_live_slot = live_slot.clone();
+        bound_live_slot.add_to_bind_list(sig_info.location());
+        bound_live_slot.add_to_bind_list(gone_signal.location());
+        let mut orphaned_slot = new_test_slot_info(&address, b"orphaned");
+        orphaned_slot.add_to_bind_list(other_gone_signal.location());
+        let unbound_slot = new_test_slot_info(&address, b"unbound");
+        state_db
+            .set_signal_info(&address, &b"signal".to_vec(), &sig_info, None)
+            .unwrap();
+        for slot_info in &[&bound_live_slot, &orphaned_slot, &unbound_slot] {
+            let slot_key = slot_info.location().slot_key().clone();
+            state_db
+                .set_slot_info(&address, &slot_key, slot_info, None)
+                .unwrap();
+        }
+
+        let report = state_db.gc_signal_slot_state(&address, None).unwrap();
+        assert_eq!(
+            report,
+            GcReport {
+                removed_slot_bindings: vec![(
+                    sig_info.location().clone(),
+                    gone_slot.location().clone()
+                )],
+                removed_signal_bindings: vec![
+                    (
+                        live_slot.location().clone(),
+                        gone_signal.location().clone()
+                    ),
+                    (
+                        orphaned_slot.location().clone(),
+                        other_gone_signal.location().clone()
+                    ),
+                ],
+                deleted_slots: vec![orphaned_slot.location().clone()],
+            }
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+
+        let mut expected_sig_info = SignalInfo::new(&address, b"signal");
+        expected_sig_info.add_to_slot_list(&live_slot).unwrap();
+        assert_eq!(
+            state_db.get_signal_info(&address, &b"signal".to_vec()).unwrap(),
+            Some(expected_sig_info)
+        );
+        let mut expected_live_slot = live_slot.clone();
+        expected_live_slot.add_to_bind_list(sig_info.location());
+        assert_eq!(
+            state_db.get_slot_info(&address, &b"live".to_vec()).unwrap(),
+            Some(expected_live_slot)
+        );
+        assert_eq!(
+            state_db.get_slot_info(&address, &b"orphaned".to_vec()).unwrap(),
+            None
+        );
+        assert_eq!(
+            state_db.get_slot_info(&address, &b"unbound".to_vec()).unwrap(),
+            Some(unbound_slot)
+        );
+        assert!(state_db
+            .gc_signal_slot_state(&address, None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_emission_touched_contracts() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let emitter = Address::from_low_u64_be(1);
+        let signal_key = b"signal".to_vec();
+        assert!(state_db
+            .emission_touched_contracts(&emitter, &signal_key)
+            .unwrap()
+            .is_empty());
+
+        let mut sig_info = SignalInfo::new(&emitter, &signal_key);
+        for (owner, slot_key) in &[(3, b"a"), (2, b"a"), (3, b"b")] {
+            let owner = Address::from_low_u64_be(*owner);
+            sig_info
+                .add_to_slot_list(&new_test_slot_info(&owner, *slot_key))
+                .unwrap();
+        }
+        state_db
+            .set_signal_info(&emitter, &signal_key, &sig_info, None)
+            .unwrap();
+        assert_eq!(
+            state_db
+                .emission_touched_contracts(&emitter, &signal_key)
+                .unwrap(),
+            vec![Address::from_low_u64_be(3), Address::from_low_u64_be(2)]
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_global_slot_tx_queues_in_range() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = new_test_slot_info(&owner, b"slot");
+        let new_queue = |epoch_height: u64| {
+            let mut queue = SlotTxQueue::new();
+            queue.enqueue(SlotTx::new(
+                &Slot::new(&slot_info),
+                &epoch_height,
+                &vec![],
+            ));
+            queue
+        };
+        // 256 and 1 share the first little endian byte.
+        for epoch_height in &[256u64, 1, 7, 3] {
+            state_db
+                .set_global_slot_tx_queue(
+                    *epoch_height,
+                    &new_queue(*epoch_height),
+                    None,
+                )
+                .unwrap();
+        }
+        state_db
+            .set_global_slot_tx_queue(5, &SlotTxQueue::new(), None)
+            .unwrap();
+
+        assert_eq!(
+            state_db.get_global_slot_tx_queues_in_range(1, 256).unwrap(),
+            vec![
+                (1, new_queue(1)),
+                (3, new_queue(3)),
+                (7, new_queue(7)),
+                (256, new_queue(256)),
+            ]
+        );
+        assert_eq!(
+            state_db.get_global_slot_tx_queues_in_range(2, 6).unwrap(),
+            vec![(3, new_queue(3))]
+        );
+        assert!(state_db
+            .get_global_slot_tx_queues_in_range(8, 255)
+            .unwrap()
+            .is_empty());
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_build_slot_tx() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let emitter = Address::from_low_u64_be(1);
+        let owner = Address::from_low_u64_be(2);
+        let signal_key = b"signal".to_vec();
+        let raw_data = vec![1u8, 2u8];
+        let slot_info = new_test_slot_info(&owner, b"slot");
+        let bound = slot_info.location().clone();
+        let unbound = SlotLocation::new(&owner, b"other");
+        assert_eq!(
+            state_db
+                .build_slot_tx(&emitter, &signal_key, &bound, 5, &raw_data)
+                .unwrap(),
+            None
+        );
+
+        let mut sig_info = SignalInfo::new(&emitter, &signal_key);
+        sig_info.add_to_slot_list(&slot_info).unwrap();
+        state_db
+            .set_signal_info(&emitter, &signal_key, &sig_info, None)
+            .unwrap();
+        assert_eq!(
+            state_db
+                .build_slot_tx(&emitter, &signal_key, &bound, 5, &raw_data)
+                .unwrap(),
+            Some(SlotTx::new(&Slot::new(&slot_info), &5, &raw_data))
+        );
+        assert_eq!(
+            state_db
+                .build_slot_tx(&emitter, &signal_key, &unbound, 5, &raw_data)
+                .unwrap(),
+            None
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_revert_to() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let key =
+            |k: &'static [u8]| StorageKey::new_storage_key(&address, k);
+        let value = |v: u8| vec![v].into_boxed_slice();
+        state_db.set_raw(key(b"a"), value(1), None).unwrap();
+
+        let outer = state_db.checkpoint();
+        state_db.set_raw(key(b"a"), value(2), None).unwrap();
+        state_db.set_raw(key(b"b"), value(2), None).unwrap();
+        let inner = state_db.checkpoint();
+        state_db.delete(key(b"a"), None).unwrap();
+        state_db.set_raw(key(b"c"), value(3), None).unwrap();
+        state_db.set_raw(key(b"c"), value(4), None).unwrap();
+
+        state_db.revert_to(inner).unwrap();
+        assert_eq!(state_db.get_raw(key(b"a")).unwrap(), Some(value(2)));
+        assert_eq!(state_db.get_raw(key(b"c")).unwrap(), None);
+        // The inner checkpoint is dropped.
+        assert!(state_db.revert_to(inner).is_err());
+
+        let inner = state_db.checkpoint();
+        state_db
+            .delete_all(StorageKey::new_storage_root_key(&address), None)
+            .unwrap();
+        assert_eq!(state_db.get_raw(key(b"b")).unwrap(), None);
+        // Reverting the outer checkpoint also reverts the inner one.
+        state_db.revert_to(outer).unwrap();
+        assert_eq!(state_db.get_raw(key(b"a")).unwrap(), Some(value(1)));
+        assert_eq!(state_db.get_raw(key(b"b")).unwrap(), None);
+        assert!(state_db.revert_to(inner).is_err());
+        assert!(state_db.revert_to(outer).is_err());
+
+        // Checkpoints are dropped on commit.
+        let checkpoint = state_db.checkpoint();
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+        assert!(state_db.revert_to(checkpoint).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let (_left_storage_manager, mut left) = new_state_db();
+        let (_right_storage_manager, mut right) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let key =
+            |k: &'static [u8]| StorageKey::new_storage_key(&address, k);
+        let value = |v: u8| vec![v].into_boxed_slice();
+        for state_db in &mut [&mut left, &mut right] {
+            state_db.set_raw(key(b"same"), value(1), None).unwrap();
+            state_db.set_raw(key(b"changed"), value(1), None).unwrap();
+            // Outside of the prefix.
+            state_db
+                .set_raw(
+                    StorageKey::new_storage_key(&other, b"changed"),
+                    value(1),
+                    None,
+                )
+                .unwrap();
+        }
+        left.set_raw(key(b"removed"), value(1), None).unwrap();
+        right.set_raw(key(b"added"), value(2), None).unwrap();
+        right.set_raw(key(b"changed"), value(2), None).unwrap();
+        right
+            .set_raw(
+                StorageKey::new_storage_key(&other, b"changed"),
+                value(2),
+                None,
+            )
+            .unwrap();
+
+        let prefix = StorageKey::new_storage_root_key(&address);
+        assert_eq!(
+            left.diff(&right, prefix).unwrap(),
+            vec![
+                StorageDiffEntry {
+                    key: key(b"added").to_key_bytes(),
+                    left: None,
+                    right: Some(value(2)),
+                },
+                StorageDiffEntry {
+                    key: key(b"changed").to_key_bytes(),
+                    left: Some(value(1)),
+                    right: Some(value(2)),
+                },
+                StorageDiffEntry {
+                    key: key(b"removed").to_key_bytes(),
+                    left: Some(value(1)),
+                    right: None,
+                },
+            ]
+        );
+        assert!(left.diff(&left, prefix).unwrap().is_empty());
+        left.commit(H256::from_low_u64_be(1)).unwrap();
+        right.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_storage_root_cache_invalidation() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let address = Address::from_low_u64_be(1);
+        let other = Address::from_low_u64_be(2);
+        let key = StorageKey::new_storage_key(&address, b"key");
+        assert_eq!(state_db.get_storage_root(&address).unwrap(), None);
+
+        state_db
+            .set_raw(key, vec![1u8].into_boxed_slice(), None)
+            .unwrap();
+        state_db
+            .set_raw(
+                StorageKey::new_storage_key(&other, b"key"),
+                vec![1u8].into_boxed_slice(),
+                None,
+            )
+            .unwrap();
+        let root = state_db.get_storage_root(&address).unwrap();
+        assert!(root.is_some());
+        assert_eq!(state_db.get_storage_root(&address).unwrap(), root);
+        let other_root = state_db.get_storage_root(&other).unwrap();
+
+        state_db
+            .set_raw(key, vec![2u8].into_boxed_slice(), None)
+            .unwrap();
+        let new_root = state_db.get_storage_root(&address).unwrap();
+        assert!(new_root.is_some());
+        assert_ne!(new_root, root);
+        assert_eq!(state_db.get_storage_root(&other).unwrap(), other_root);
+
+        state_db.delete(key, None).unwrap();
+        assert_eq!(state_db.get_storage_root(&address).unwrap(), None);
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_get_signals_for_slot() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let emitter = Address::from_low_u64_be(2);
+        let mut slot_info = new_test_slot_info(&owner, b"slot");
+        assert!(state_db.get_signals_for_slot(&slot_info).unwrap().is_empty());
+
+        let mut expected = Vec::new();
+        for signal_key in &[b"b".to_vec(), b"missing".to_vec(), b"a".to_vec()]
+        {
+            slot_info
+                .add_to_bind_list(&SignalLocation::new(&emitter, signal_key));
+            if signal_key != b"missing" {
+                let sig_info = SignalInfo::new(&emitter, signal_key);
+                state_db
+                    .set_signal_info(&emitter, signal_key, &sig_info, None)
+                    .unwrap();
+                expected.push(sig_info);
+            }
+        }
+        assert_eq!(
+            state_db.get_signals_for_slot(&slot_info).unwrap(),
+            expected
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_find_stale_slot_bindings() {
+        let (_storage_manager, mut state_db) = new_state_db();
+        let owner = Address::from_low_u64_be(1);
+        let emitter = Address::from_low_u64_be(2);
+        let signal_key = b"signal".to_vec();
+        assert!(state_db
+            .find_stale_slot_bindings(&emitter, &signal_key)
+            .unwrap()
+            .is_empty());
+
+        let current = new_test_slot_info(&owner, b"current");
+        let limit_changed = new_test_slot_info(&owner, b"limit");
+        let ratio_changed = new_test_slot_info(&owner, b"ratio");
+        let gone = new_test_slot_info(&owner, b"gone");
+        let mut sig_info = SignalInfo::new(&emitter, &signal_key);
+        for slot_info in &[&current, &limit_changed, &ratio_changed, &gone] {
+            sig_info.add_to_slot_list(slot_info).unwrap();
+        }
+        state_db
+            .set_signal_info(&emitter, &signal_key, &sig_info, None)
+            .unwrap();
+
+        // Rewrite two of the slots after binding them.
+        let rewritten = [
+            current.clone(),
+            SlotInfo::new(
+                &owner,
+                b"limit",
+                &H256::zero(),
+                &owner,
+                &U256::from(2000),
+                &U256::from(100),
+            ),
+            SlotInfo::new(
+                &owner,
+                b"ratio",
+                &H256::zero(),
+                &owner,
+                &U256::from(1000),
+                &U256::from(150),
+            ),
+        ];
+        for slot_info in &rewritten {
+            let slot_key = slot_info.location().slot_key().clone();
+            state_db
+                .set_slot_info(&owner, &slot_key, slot_info, None)
+                .unwrap();
+        }
+
+        // The current binding isn't stale, and the one whose SlotInfo is
+        // gone is dangling rather than stale.
+        assert_eq!(
+            state_db
+                .find_stale_slot_bindings(&emitter, &signal_key)
+                .unwrap(),
+            vec![
+                limit_changed.location().clone(),
+                ratio_changed.location().clone(),
+            ]
+        );
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+
+    #[test]
+    fn test_export_import_account_round_trip() {
+        let mut address = Address::from_low_u64_be(1);
+        address.set_user_account_type_bits();
+        let mut other = Address::from_low_u64_be(2);
+        other.set_user_account_type_bits();
+
+        let (_storage_manager, mut state_db) = new_state_db();
+        let account = Account::new_empty_with_balance(
+            &address,
+            &U256::from(10),
+            &1.into(),
+        );
+        state_db
+            .set::<Account>(
+                StorageKey::new_account_key(&address),
+                &account,
+                None,
+            )
+            .unwrap();
+        state_db
+            .set::<DepositList>(
+                StorageKey::new_deposit_list_key(&address),
+                &DepositList(vec![DepositInfo {
+                    amount: 5.into(),
+                    deposit_time: 1,
+                    accumulated_interest_rate: 2.into(),
+                }]),
+                None,
+            )
+            .unwrap();
+        state_db
+            .set_raw(
+                StorageKey::new_storage_key(&address, b"key"),
+                vec![1u8, 2, 3].into_boxed_slice(),
+                None,
+            )
+            .unwrap();
+        let slot_info = new_test_slot_info(&address, b"slot");
+        let mut sig_info = SignalInfo::new(&address, b"signal");
+        sig_info.add_to_slot_list(&slot_info).unwrap();
+        state_db
+            .set_slot_info(&address, &b"slot".to_vec(), &slot_info, None)
+            .unwrap();
+        state_db
+            .set_signal_info(&address, &b"signal".to_vec(), &sig_info, None)
+            .unwrap();
+        let mut queue = SlotTxQueue::new();
+        queue.enqueue(SlotTx::new(&Slot::new(&slot_info), &3, &vec![]));
+        state_db
+            .set_account_slot_tx_queue(&address, &queue, None)
+            .unwrap();
+
+        let portable = state_db.export_account(&address).unwrap().unwrap();
+        assert_eq!(portable.storage, vec![(b"key".to_vec(), vec![1u8, 2, 3])]);
+        assert_eq!(portable.signal_infos, vec![sig_info]);
+        assert_eq!(portable.slot_infos, vec![slot_info]);
+        assert!(state_db.export_account(&other).unwrap().is_none());
+
+        let encoded = ::rlp::encode(&portable);
+        let decoded = ::rlp::decode::<PortableAccount>(&encoded).unwrap();
+        assert_eq!(decoded, portable);
+
+        let (_other_storage_manager, mut imported_state_db) = new_state_db();
+        imported_state_db.import_account(&decoded, None).unwrap();
+
+        assert_eq!(
+            imported_state_db.export_account(&address).unwrap().unwrap(),
+            portable
+        );
+        assert_eq!(
+            imported_state_db.partial_state_root(&[address]).unwrap(),
+            state_db.partial_state_root(&[address]).unwrap()
+        );
+
+        state_db.commit(H256::from_low_u64_be(1)).unwrap();
+        imported_state_db.commit(H256::from_low_u64_be(1)).unwrap();
+    }
+}
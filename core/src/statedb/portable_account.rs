@@ -0,0 +1,105 @@
+// Copyright 2019 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use primitives::{Account, CodeInfo, DepositList, VoteStakeList};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+
+//////////////////////////////////////////////////////////////////////
+/* Signal and Slots begin */
+use primitives::{SignalInfo, SlotInfo, SlotTxQueue};
+/* Signal and Slots end */
+//////////////////////////////////////////////////////////////////////
+
+/// The complete state of an account, exported by `StateDb::export_account`
+/// and written back by `StateDb::import_account`. It is RLP serializable so
+/// that an account can be moved between networks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortableAccount {
+    pub account: Account,
+    /// The code referred to by the code hash of the account, if any.
+    pub code: Option<CodeInfo>,
+    pub deposit_list: Option<DepositList>,
+    pub vote_stake_list: Option<VoteStakeList>,
+    /// Raw storage entries as (storage key, value) pairs, sorted by key. The
+    /// entry with the empty key is the storage layout.
+    pub storage: Vec<(Vec<u8>, Vec<u8>)>,
+
+    //////////////////////////////////////////////////////////////////////
+    /* Signal and Slots begin */
+    pub signal_infos: Vec<SignalInfo>,
+    pub slot_infos: Vec<SlotInfo>,
+    pub slot_tx_queue: Option<SlotTxQueue>,
+    /* Signal and Slots end */
+    //////////////////////////////////////////////////////////////////////
+}
+
+impl PortableAccount {
+    const RLP_ITEM_COUNT: usize = 8;
+}
+
+// Optional values are encoded as lists of zero or one item.
+fn append_optional<T: Encodable>(stream: &mut RlpStream, value: &Option<T>) {
+    match value {
+        Some(value) => {
+            stream.begin_list(1).append(value);
+        }
+        None => {
+            stream.begin_list(0);
+        }
+    }
+}
+
+fn decode_optional<T: Decodable>(
+    rlp: &Rlp, index: usize,
+) -> Result<Option<T>, DecoderError> {
+    let item = rlp.at(index)?;
+    match item.item_count()? {
+        0 => Ok(None),
+        1 => Ok(Some(item.val_at(0)?)),
+        _ => Err(DecoderError::RlpIncorrectListLen),
+    }
+}
+
+impl Encodable for PortableAccount {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream
+            .begin_list(Self::RLP_ITEM_COUNT)
+            .append(&self.account);
+        append_optional(stream, &self.code);
+        append_optional(stream, &self.deposit_list);
+        append_optional(stream, &self.vote_stake_list);
+        stream.begin_list(self.storage.len());
+        for (key, value) in &self.storage {
+            stream.begin_list(2).append(key).append(value);
+        }
+        stream.append_list(&self.signal_infos);
+        stream.append_list(&self.slot_infos);
+        append_optional(stream, &self.slot_tx_queue);
+    }
+}
+
+impl Decodable for PortableAccount {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != Self::RLP_ITEM_COUNT {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut storage = Vec::new();
+        for entry in rlp.at(4)?.iter() {
+            if entry.item_count()? != 2 {
+                return Err(DecoderError::RlpIncorrectListLen);
+            }
+            storage.push((entry.val_at(0)?, entry.val_at(1)?));
+        }
+        Ok(PortableAccount {
+            account: rlp.val_at(0)?,
+            code: decode_optional(rlp, 1)?,
+            deposit_list: decode_optional(rlp, 2)?,
+            vote_stake_list: decode_optional(rlp, 3)?,
+            storage,
+            signal_infos: rlp.list_at(5)?,
+            slot_infos: rlp.list_at(6)?,
+            slot_tx_queue: decode_optional(rlp, 7)?,
+        })
+    }
+}
@@ -422,38 +422,8 @@ impl StateTrait for State {
         };
 
         // Retrieve key/value pairs from snapshot
-        let mut kv_iterator = self.snapshot_db.snapshot_kv_iterator()?;
-        let lower_bound_incl = access_key_prefix.to_key_bytes();
-        let mut upper_bound_excl_value = lower_bound_incl.clone();
-        let upper_bound_excl = if lower_bound_incl.len() == 0 {
-            None
-        } else {
-            let mut carry = 1;
-            let len = upper_bound_excl_value.len();
-            for i in 0..len {
-                if upper_bound_excl_value[len - 1 - i] == 255 {
-                    upper_bound_excl_value[len - 1 - i] = 0;
-                } else {
-                    upper_bound_excl_value[len - 1 - i] += 1;
-                    carry = 0;
-                    break;
-                }
-            }
-            // all bytes in lower_bound_incl are 255, which means no upper bound
-            // is needed.
-            if carry == 1 {
-                None
-            } else {
-                Some(upper_bound_excl_value.as_slice())
-            }
-        };
-        let mut kvs = kv_iterator
-            .iter_range(lower_bound_incl.as_slice(), upper_bound_excl)?;
-
-        let mut snapshot_kvs = Vec::new();
-        while let Some((key, value)) = kvs.next()? {
-            snapshot_kvs.push((key, value));
-        }
+        let snapshot_kvs =
+            self.get_all_from_snapshot(Some(access_key_prefix))?;
 
         let mut result = Vec::new();
         // This is used to keep track of the deleted keys.
@@ -503,6 +473,73 @@ impl StateTrait for State {
         }
     }
 
+    /// Read all key/value pairs with access_key_prefix as prefix without
+    /// modifying the state. The same three places as `delete_all` are
+    /// visited. A key/value pair in Delta Trie shadows the one in
+    /// Intermediate Trie, which in turn shadows the one in Snapshot DB, so
+    /// a tombstone hides the older values. The result is sorted by key.
+    fn read_all(
+        &self, access_key_prefix: StorageKey,
+    ) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>> {
+        self.read_all_with_prefix(Some(access_key_prefix))
+    }
+
+    /// Read all key/value pairs of the state in the same way as `read_all`.
+    /// This is a full scan over Delta Trie, Intermediate Trie and Snapshot
+    /// DB.
+    fn read_all_state(&self) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>> {
+        self.read_all_with_prefix(None)
+    }
+
+    /// Read the key/value pairs with access_key_prefix as prefix whose keys
+    /// after the prefix fall in [start_key, end_key), sorted by key and at
+    /// most limit of them. The tries can only be traversed by prefix, so
+    /// their key/value pairs under the prefix are filtered by the range.
+    /// Snapshot DB is read by the range, and only until there are limit
+    /// key/value pairs which aren't shadowed by the tries.
+    fn read_range(
+        &self, access_key_prefix: StorageKey, start_key: &[u8],
+        end_key: &[u8], limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Box<[u8]>)>>
+    {
+        let key_prefix = access_key_prefix.to_key_bytes();
+        let mut lower_bound_incl = key_prefix.clone();
+        lower_bound_incl.extend_from_slice(start_key);
+        let mut upper_bound_excl = key_prefix;
+        upper_bound_excl.extend_from_slice(end_key);
+        if limit == 0 || lower_bound_incl >= upper_bound_excl {
+            return Ok(vec![]);
+        }
+
+        let trie_kvs: BTreeMap<_, _> = self
+            .read_all_from_tries(Some(access_key_prefix))?
+            .into_iter()
+            .filter(|(k, _)| *k >= lower_bound_incl && *k < upper_bound_excl)
+            .collect();
+
+        let mut result = Vec::new();
+        let mut kv_iterator = self.snapshot_db.snapshot_kv_iterator()?;
+        let mut kvs = kv_iterator.iter_range(
+            lower_bound_incl.as_slice(),
+            Some(upper_bound_excl.as_slice()),
+        )?;
+        while result.len() < limit {
+            match kvs.next()? {
+                Some((k, v)) => {
+                    if !trie_kvs.contains_key(&k) {
+                        result.push((k, v));
+                    }
+                }
+                None => break,
+            }
+        }
+
+        result.extend(trie_kvs.into_iter().filter(|(_, v)| v.len() > 0));
+        result.sort_by(|a, b| a.0.cmp(&b.0));
+        result.truncate(limit);
+        Ok(result)
+    }
+
     fn compute_state_root(&mut self) -> Result<StateRootWithAuxInfo> {
         self.ensure_temp_slab_for_db_load();
 
@@ -581,6 +618,164 @@ impl StateTrait for State {
 }
 
 impl State {
+    /// Implementation of `read_all` and `read_all_state`. No prefix means
+    /// the whole state.
+    fn read_all_with_prefix(
+        &self, maybe_access_key_prefix: Option<StorageKey>,
+    ) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>> {
+        let trie_kvs = self.read_all_from_tries(maybe_access_key_prefix)?;
+
+        // Retrieve key/value pairs from snapshot
+        let snapshot_kvs =
+            self.get_all_from_snapshot(maybe_access_key_prefix)?;
+
+        let visited_keys: HashSet<_> =
+            trie_kvs.iter().map(|(k, _)| k.clone()).collect();
+        let mut result: Vec<_> =
+            trie_kvs.into_iter().filter(|(_, v)| v.len() > 0).collect();
+
+        // No need to check v.len() because there are no tombStone values in
+        // snapshot.
+        for (k, v) in snapshot_kvs {
+            if !visited_keys.contains(&k) {
+                result.push((k, v));
+            }
+        }
+
+        if result.is_empty() {
+            Ok(None)
+        } else {
+            result.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(Some(result))
+        }
+    }
+
+    /// Retrieve the key/value pairs with access_key_prefix as prefix from
+    /// Delta Trie and Intermediate Trie, with the keys as storage key bytes.
+    /// A key found in Delta Trie shadows the one in Intermediate Trie, and
+    /// tombstones are kept as empty values so that they can shadow the
+    /// snapshot. No prefix means all key/value pairs.
+    fn read_all_from_tries(
+        &self, maybe_access_key_prefix: Option<StorageKey>,
+    ) -> Result<Vec<(Vec<u8>, Box<[u8]>)>> {
+        self.ensure_temp_slab_for_db_load();
+        // Traversal won't create any new nodes so it's fine to pass an empty
+        // owned_node_set.
+        let mut empty_owned_node_set: Option<OwnedNodeSet> =
+            Some(Default::default());
+
+        // Retrieve key/value pairs from delta trie
+        let delta_trie_kvs = match &self.delta_trie_root {
+            None => None,
+            Some(root_node) => {
+                let delta_mpt_key_prefix = match maybe_access_key_prefix {
+                    Some(access_key_prefix) => access_key_prefix
+                        .to_delta_mpt_key_bytes(&self.delta_trie_key_padding),
+                    None => vec![],
+                };
+                SubTrieVisitor::new(
+                    &self.delta_trie,
+                    root_node.clone(),
+                    &mut empty_owned_node_set,
+                )?
+                .traversal(&delta_mpt_key_prefix, &delta_mpt_key_prefix)?
+            }
+        };
+
+        // Retrieve key/value pairs from intermediate trie
+        let intermediate_trie_kvs = match (
+            &self.intermediate_trie_root,
+            &self.maybe_intermediate_trie,
+            &self.maybe_intermediate_trie_key_padding,
+        ) {
+            (
+                Some(root_node),
+                Some(intermediate_trie),
+                Some(intermediate_trie_key_padding),
+            ) => {
+                let intermediate_mpt_key_prefix =
+                    match maybe_access_key_prefix {
+                        Some(access_key_prefix) => access_key_prefix
+                            .to_delta_mpt_key_bytes(
+                                intermediate_trie_key_padding,
+                            ),
+                        None => vec![],
+                    };
+                SubTrieVisitor::new(
+                    intermediate_trie,
+                    root_node.clone(),
+                    &mut empty_owned_node_set,
+                )?
+                .traversal(
+                    &intermediate_mpt_key_prefix,
+                    &intermediate_mpt_key_prefix,
+                )?
+            }
+            _ => None,
+        };
+
+        let mut result = Vec::new();
+        // This is used to keep track of the keys already found in a newer
+        // trie.
+        let mut visited_keys = HashSet::new();
+        for kvs in vec![delta_trie_kvs, intermediate_trie_kvs] {
+            if let Some(kvs) = kvs {
+                for (k, v) in kvs {
+                    let k = StorageKey::from_delta_mpt_key(&k).to_key_bytes();
+                    if visited_keys.insert(k.clone()) {
+                        result.push((k, v));
+                    }
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Retrieve all key/value pairs with access_key_prefix as prefix from
+    /// the snapshot db. No prefix means all key/value pairs.
+    fn get_all_from_snapshot(
+        &self, maybe_access_key_prefix: Option<StorageKey>,
+    ) -> Result<Vec<(Vec<u8>, Box<[u8]>)>> {
+        let mut kv_iterator = self.snapshot_db.snapshot_kv_iterator()?;
+        let lower_bound_incl = match maybe_access_key_prefix {
+            Some(access_key_prefix) => access_key_prefix.to_key_bytes(),
+            None => vec![],
+        };
+        let mut upper_bound_excl_value = lower_bound_incl.clone();
+        let upper_bound_excl = if lower_bound_incl.len() == 0 {
+            None
+        } else {
+            let mut carry = 1;
+            let len = upper_bound_excl_value.len();
+            for i in 0..len {
+                if upper_bound_excl_value[len - 1 - i] == 255 {
+                    upper_bound_excl_value[len - 1 - i] = 0;
+                } else {
+                    upper_bound_excl_value[len - 1 - i] += 1;
+                    carry = 0;
+                    break;
+                }
+            }
+            // all bytes in lower_bound_incl are 255, which means no upper bound
+            // is needed.
+            if carry == 1 {
+                None
+            } else {
+                Some(upper_bound_excl_value.as_slice())
+            }
+        };
+        let mut kvs = kv_iterator
+            .iter_range(lower_bound_incl.as_slice(), upper_bound_excl)?;
+
+        let mut snapshot_kvs = Vec::new();
+        while let Some((key, value)) = kvs.next()? {
+            snapshot_kvs.push((key, value));
+        }
+
+        Ok(snapshot_kvs)
+    }
+
     fn ensure_temp_slab_for_db_load(&self) {
         self.delta_trie.get_node_memory_manager().enlarge().ok();
     }
@@ -817,6 +1012,47 @@ impl State {
 
         inserter.iterate(dumper)
     }
+
+    /// Open another view of the same tries, for reading only. Committed trie
+    /// nodes are copy-on-write, so later modifications of this State aren't
+    /// visible in the view. Nodes modified but not committed yet are
+    /// changed in place, so there is no view of a dirty State and None is
+    /// returned.
+    ///
+    /// The view holds references to the snapshot db and the delta tries,
+    /// which keeps them from being released while the state manager moves on
+    /// to newer snapshots, so it shouldn't be kept for long.
+    pub fn readonly_snapshot(&self) -> Option<State> {
+        if self.dirty {
+            return None;
+        }
+        if let Some(NodeRefDeltaMpt::Dirty { .. }) = self.delta_trie_root {
+            return None;
+        }
+        Some(Self {
+            manager: self.manager.clone(),
+            snapshot_db: self.snapshot_db.clone(),
+            snapshot_epoch_id: self.snapshot_epoch_id,
+            snapshot_merkle_root: self.snapshot_merkle_root,
+            maybe_intermediate_trie: self.maybe_intermediate_trie.clone(),
+            intermediate_trie_root: self.intermediate_trie_root.clone(),
+            intermediate_trie_root_merkle: self.intermediate_trie_root_merkle,
+            maybe_intermediate_trie_key_padding: self
+                .maybe_intermediate_trie_key_padding
+                .clone(),
+            delta_trie: self.delta_trie.clone(),
+            delta_trie_root: self.delta_trie_root.clone(),
+            delta_trie_key_padding: self.delta_trie_key_padding.clone(),
+            intermediate_epoch_id: self.intermediate_epoch_id,
+            delta_trie_height: self.delta_trie_height,
+            height: self.height,
+            owned_node_set: Some(Default::default()),
+            dirty: false,
+            children_merkle_map: ChildrenMerkleMap::new(),
+
+            parent_epoch_id: self.parent_epoch_id,
+        })
+    }
 }
 
 use crate::storage::{
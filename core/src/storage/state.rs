@@ -33,6 +33,20 @@ pub trait StateTrait {
     fn delete_all(
         &mut self, access_key_prefix: StorageKey,
     ) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>>;
+    // Read everything prefixed by access_key without modifying the state.
+    fn read_all(
+        &self, access_key_prefix: StorageKey,
+    ) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>>;
+    // Read everything in the state without modifying it. This is a full
+    // scan.
+    fn read_all_state(&self) -> Result<Option<Vec<(Vec<u8>, Box<[u8]>)>>>;
+    // Read the key/value pairs prefixed by access_key_prefix whose keys after
+    // the prefix fall in [start_key, end_key), sorted by key and at most limit
+    // of them, without modifying the state.
+    fn read_range(
+        &self, access_key_prefix: StorageKey, start_key: &[u8],
+        end_key: &[u8], limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Box<[u8]>)>>;
 
     // Finalize
     /// It's costly to compute state root however it's only necessary to compute
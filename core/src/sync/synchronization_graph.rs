@@ -1600,7 +1600,7 @@ impl SynchronizationGraph {
                 if need_to_verify && !self.is_consortium() {
                     // Compute pow_quality, because the input header may be used
                     // as a part of block later
-                    VerificationConfig::compute_pow_hash_and_fill_header_pow_quality(header);
+                    VerificationConfig::compute_pow_hash_and_fill_header_pow_quality_with(header, self.verification_config.pow_lower_bound_bits);
                 }
                 return (
                     BlockHeaderInsertionResult::AlreadyProcessed,
@@ -1613,7 +1613,7 @@ impl SynchronizationGraph {
             if need_to_verify {
                 // Compute pow_quality, because the input header may be used as
                 // a part of block later
-                VerificationConfig::compute_pow_hash_and_fill_header_pow_quality(header);
+                VerificationConfig::compute_pow_hash_and_fill_header_pow_quality_with(header, self.verification_config.pow_lower_bound_bits);
             }
             return (BlockHeaderInsertionResult::AlreadyProcessed, Vec::new());
         }
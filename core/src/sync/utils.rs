@@ -14,6 +14,7 @@ use crate::{
         consensus_internal::INITIAL_BASE_MINING_REWARD_IN_UCFX,
         WORKER_COMPUTATION_PARALLELISM,
     },
+    parameters::pow::POW_LOWER_BOUND_BITS,
     pow::ProofOfWorkConfig,
     statistics::Statistics,
     storage::{StorageConfiguration, StorageManager},
@@ -137,6 +138,7 @@ pub fn initialize_data_manager(
                                              * address */
             Duration::from_millis(300_000), /* max cached tx count */
             dbtype,
+            POW_LOWER_BOUND_BITS,
         ),
     ));
     (data_man, genesis_block)
@@ -152,6 +154,7 @@ pub fn initialize_synchronization_graph_with_data_manager(
         REFEREE_DEFAULT_BOUND,
         MAX_BLOCK_SIZE_IN_BYTES,
         TRANSACTION_DEFAULT_EPOCH_BOUND,
+        POW_LOWER_BOUND_BITS,
     );
 
     let machine = Arc::new(new_machine_with_builtin());
@@ -172,6 +175,7 @@ pub fn initialize_synchronization_graph_with_data_manager(
         String::from(""), /* stratum_listen_addr */
         0,                /* stratum_port */
         None,             /* stratum_secret */
+        None,             /* adjustment_factor */
     );
     let sync_config = SyncGraphConfig {
         enable_state_expose: false,
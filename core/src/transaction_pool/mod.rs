@@ -841,13 +841,14 @@ impl TransactionPool {
             // Pack all slot tx that are not packed before.
             for idx in 0..size
             {
-                let mut tx = queue.peek(idx).unwrap().clone();
+                let tx = queue.peek(idx).unwrap();
                 // skip if gas is not set yet
                 if tx.gas().is_zero() {
                     continue;
                 }
 
                 // Set gas price.
+                let mut tx = tx.clone();
                 tx.calculate_and_set_gas_price(&average_gas_price);
 
                 // Save gas limit.
@@ -865,7 +866,7 @@ impl TransactionPool {
                         epoch_height: 0,
                         chain_id: 0,
                         data: Vec::new(),
-                        slot_tx: Some(tx.clone()),
+                        slot_tx: Some(tx),
                     }
                 );
 
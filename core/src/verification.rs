@@ -5,8 +5,8 @@
 use crate::{
     error::{BlockError, Error},
     executive::Executive,
-    parameters::block::*,
-    pow::{self, nonce_to_lower_bound, ProofOfWorkProblem},
+    parameters::{block::*, pow::POW_LOWER_BOUND_BITS},
+    pow::{self, nonce_to_lower_bound_with, ProofOfWorkProblem},
     storage::{make_simple_mpt, simple_mpt_merkle_root, TrieProof},
     sync::{Error as SyncError, ErrorKind as SyncErrorKind},
     vm,
@@ -26,6 +26,8 @@ pub struct VerificationConfig {
     pub referee_bound: usize,
     pub max_block_size_in_bytes: usize,
     pub transaction_epoch_bound: u64,
+    // Must match `ProofOfWorkConfig::lower_bound_bits`.
+    pub pow_lower_bound_bits: u32,
     vm_spec: vm::Spec,
 }
 
@@ -72,7 +74,7 @@ pub fn verify_tx_receipt_inclusion_proof(
 impl VerificationConfig {
     pub fn new(
         test_mode: bool, referee_bound: usize, max_block_size_in_bytes: usize,
-        transaction_epoch_bound: u64,
+        transaction_epoch_bound: u64, pow_lower_bound_bits: u32,
     ) -> Self
     {
         if test_mode {
@@ -81,6 +83,7 @@ impl VerificationConfig {
                 referee_bound,
                 max_block_size_in_bytes,
                 transaction_epoch_bound,
+                pow_lower_bound_bits,
                 vm_spec: vm::Spec::new_spec(),
             }
         } else {
@@ -89,6 +92,7 @@ impl VerificationConfig {
                 referee_bound,
                 max_block_size_in_bytes,
                 transaction_epoch_bound,
+                pow_lower_bound_bits,
                 vm_spec: vm::Spec::new_spec(),
             }
         }
@@ -98,17 +102,30 @@ impl VerificationConfig {
     /// Note that this function returns *pow_hash* of the block, not its quality
     pub fn compute_pow_hash_and_fill_header_pow_quality(
         header: &mut BlockHeader,
+    ) -> H256 {
+        Self::compute_pow_hash_and_fill_header_pow_quality_with(
+            header,
+            POW_LOWER_BOUND_BITS,
+        )
+    }
+
+    #[inline]
+    pub fn compute_pow_hash_and_fill_header_pow_quality_with(
+        header: &mut BlockHeader, lower_bound_bits: u32,
     ) -> H256 {
         let nonce = header.nonce();
         let pow_hash = pow::compute(&nonce, &header.problem_hash());
-        header.pow_quality = pow::pow_hash_to_quality(&pow_hash, &nonce);
+        header.pow_quality =
+            pow::pow_hash_to_quality_with(&pow_hash, &nonce, lower_bound_bits);
         pow_hash
     }
 
     #[inline]
     pub fn verify_pow(&self, header: &mut BlockHeader) -> Result<(), Error> {
-        let pow_hash =
-            Self::compute_pow_hash_and_fill_header_pow_quality(header);
+        let pow_hash = Self::compute_pow_hash_and_fill_header_pow_quality_with(
+            header,
+            self.pow_lower_bound_bits,
+        );
         if header.difficulty().is_zero() {
             return Err(BlockError::InvalidDifficulty(OutOfBounds {
                 min: Some(0.into()),
@@ -118,12 +135,16 @@ impl VerificationConfig {
             .into());
         }
         let boundary = pow::difficulty_to_boundary(header.difficulty());
-        if !ProofOfWorkProblem::validate_hash_against_boundary(
+        if !ProofOfWorkProblem::validate_hash_against_boundary_with(
             &pow_hash,
             &header.nonce(),
             &boundary,
+            self.pow_lower_bound_bits,
         ) {
-            let lower_bound = nonce_to_lower_bound(&header.nonce());
+            let lower_bound = nonce_to_lower_bound_with(
+                &header.nonce(),
+                self.pow_lower_bound_bits,
+            );
             // Because the lower_bound first bit is always zero, as long as the
             // difficulty is not 1, this should not overflow.
             // We just use overflowing_add() here to be safe.
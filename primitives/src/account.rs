@@ -3,7 +3,7 @@
 // See http://www.gnu.org/licenses/
 
 use crate::{bytes::Bytes, hash::KECCAK_EMPTY};
-use cfx_types::{address_util::AddressUtil, Address, H256, U256};
+use cfx_types::{address_util::AddressUtil, Address, H256, U256, U512};
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use rlp_derive::{
     RlpDecodable, RlpDecodableWrapper, RlpEncodable, RlpEncodableWrapper,
@@ -120,6 +120,19 @@ impl SlotTxQueue {
         self.list.push(slot_tx);
     }
 
+    // Enqueue slot_tx unless the queue already holds a duplicate of it, see
+    // SlotTx::is_duplicated, e.g. when an emission is replayed after a
+    // reorg. Returns whether it was enqueued. This scans the whole queue, so
+    // it is O(n). Callers which can't produce duplicates, such as filling a
+    // new queue from a single emission, should use enqueue instead.
+    pub fn enqueue_dedup(&mut self, slot_tx: SlotTx) -> bool {
+        if self.list.iter().any(|tx| tx.is_duplicated(&slot_tx)) {
+            return false;
+        }
+        self.list.push(slot_tx);
+        true
+    }
+
     pub fn dequeue(&mut self) -> Option<SlotTx> {
         if self.list.is_empty() {
             return None;
@@ -149,6 +162,102 @@ impl SlotTxQueue {
     pub fn len(&self) -> usize {
         self.list.len()
     }
+
+    pub fn iter(&self) -> std::slice::Iter<SlotTx> {
+        self.list.iter()
+    }
+
+    // Sum of the gas limits of all queued slot transactions. It is
+    // accumulated in U512 so adversarially large gas limits can't overflow.
+    pub fn total_gas_limit(&self) -> U512 {
+        self.list.iter().fold(U512::zero(), |total, tx| {
+            total + U512::from(*tx.gas_limit())
+        })
+    }
+
+    // One line summary of the queue for logging: the number of
+    // transactions, the total gas limit, the number of distinct epochs and
+    // the contracts with the most transactions.
+    pub fn summary(&self) -> String {
+        use std::fmt::Write;
+        const TOP_CONTRACTS: usize = 3;
+
+        let mut epochs: Vec<u64> =
+            self.list.iter().map(|tx| tx.epoch_height()).collect();
+        epochs.sort_unstable();
+        epochs.dedup();
+
+        let mut contracts: Vec<(&Address, usize)> = Vec::new();
+        for tx in &self.list {
+            match contracts.iter_mut().find(|(addr, _)| *addr == tx.address()) {
+                Some((_, count)) => *count += 1,
+                None => contracts.push((tx.address(), 1)),
+            }
+        }
+        contracts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        let mut summary = format!(
+            "len={} total_gas_limit={} epochs={} top_contracts=[",
+            self.list.len(),
+            self.total_gas_limit(),
+            epochs.len()
+        );
+        for (i, (addr, count)) in
+            contracts.iter().take(TOP_CONTRACTS).enumerate()
+        {
+            if i > 0 {
+                summary.push_str(", ");
+            }
+            // Writing to a String never fails.
+            write!(summary, "{:?}: {}", addr, count).ok();
+        }
+        summary.push(']');
+        summary
+    }
+
+    // Set the gas price of every queued slot transaction from the average gas
+    // price, and return the total cost of the gas limits at those prices. The
+    // total saturates at U512::MAX.
+    pub fn set_gas_prices(&mut self, average_gas_price: &U256) -> U512 {
+        let mut total_cost = U512::zero();
+        for tx in &mut self.list {
+            tx.calculate_and_set_gas_price(average_gas_price);
+            let cost = U512::from(*tx.gas_price()) * U512::from(*tx.gas_limit());
+            let (sum, overflow) = total_cost.overflowing_add(cost);
+            total_cost = if overflow { U512::max_value() } else { sum };
+        }
+        total_cost
+    }
+
+    // The index of every queued slot transaction paired with its effective
+    // gas price at the average gas price, highest price first. Transactions
+    // with the same price keep their queue order.
+    pub fn priority_ranking(
+        &self, average_gas_price: &U256,
+    ) -> Vec<(usize, U256)> {
+        let mut ranking: Vec<_> = self
+            .list
+            .iter()
+            .map(|tx| tx.effective_gas_price(average_gas_price))
+            .enumerate()
+            .collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1));
+        ranking
+    }
+
+    // Number of slot transactions from the front of the queue whose gas
+    // limits fit within the budget altogether.
+    pub fn pack_within_budget(&self, budget: &U256) -> usize {
+        let budget = U512::from(*budget);
+        let mut total = U512::zero();
+        for (idx, tx) in self.list.iter().enumerate() {
+            total = total + U512::from(*tx.gas_limit());
+            if total > budget {
+                return idx;
+            }
+        }
+        self.list.len()
+    }
 }
 
 #[derive(
@@ -364,3 +473,151 @@ impl Encodable for Account {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{Slot, SlotInfo};
+
+    fn new_slot_tx(gas_limit: U256) -> SlotTx {
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = SlotInfo::new(
+            &owner,
+            &[0x01u8],
+            &H256::zero(),
+            &owner,
+            &gas_limit,
+            &U256::from(100),
+        );
+        SlotTx::new(&Slot::new(&slot_info), &1, &vec![])
+    }
+
+    #[test]
+    fn test_slot_tx_queue_set_gas_prices() {
+        let mut queue = SlotTxQueue::new();
+        queue.enqueue(new_slot_tx(U256::from(10)));
+        queue.enqueue(new_slot_tx(U256::MAX));
+        assert_eq!(
+            queue.set_gas_prices(&U256::from(3)),
+            U512::from(30) + U512::from(U256::MAX) * U512::from(3)
+        );
+        assert!(queue.iter().all(|tx| *tx.gas_price() == U256::from(3)));
+    }
+
+    #[test]
+    fn test_slot_tx_queue_set_gas_prices_saturates() {
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = SlotInfo::new(
+            &owner,
+            &[0x01u8],
+            &H256::zero(),
+            &owner,
+            &U256::MAX,
+            &U256::MAX,
+        );
+        let slot_tx = SlotTx::new(&Slot::new(&slot_info), &1, &vec![]);
+        let mut queue = SlotTxQueue::new();
+        queue.enqueue(slot_tx.clone());
+        queue.enqueue(slot_tx);
+        assert_eq!(queue.set_gas_prices(&U256::MAX), U512::max_value());
+        assert!(queue.iter().all(|tx| *tx.gas_price() == U256::MAX));
+    }
+
+    #[test]
+    fn test_slot_tx_queue_enqueue_dedup() {
+        let mut queue = SlotTxQueue::new();
+        assert!(queue.enqueue_dedup(new_slot_tx(U256::from(10))));
+        // Gas limits are not compared.
+        assert!(!queue.enqueue_dedup(new_slot_tx(U256::from(20))));
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = SlotInfo::new(
+            &owner,
+            &[0x01u8],
+            &H256::zero(),
+            &owner,
+            &U256::from(10),
+            &U256::from(100),
+        );
+        let slot = Slot::new(&slot_info);
+        assert!(queue.enqueue_dedup(SlotTx::new(&slot, &2, &vec![])));
+        assert!(queue.enqueue_dedup(SlotTx::new(&slot, &1, &vec![0x01u8])));
+        assert!(!queue.enqueue_dedup(SlotTx::new(&slot, &1, &vec![0x01u8])));
+        assert_eq!(queue.len(), 3);
+    }
+
+    #[test]
+    fn test_slot_tx_queue_priority_ranking() {
+        let owner = Address::from_low_u64_be(1);
+        let new_slot_tx_with_ratio = |gas_ratio: U256| {
+            let slot_info = SlotInfo::new(
+                &owner,
+                &[0x01u8],
+                &H256::zero(),
+                &owner,
+                &U256::from(10),
+                &gas_ratio,
+            );
+            SlotTx::new(&Slot::new(&slot_info), &1, &vec![])
+        };
+        let mut queue = SlotTxQueue::new();
+        assert!(queue.priority_ranking(&U256::from(10)).is_empty());
+        // The denominator of the gas ratio is 100.
+        for gas_ratio in &[100u64, 150, 100, 200] {
+            queue.enqueue(new_slot_tx_with_ratio(U256::from(*gas_ratio)));
+        }
+        queue.enqueue(new_slot_tx_with_ratio(U256::MAX));
+        assert_eq!(
+            queue.priority_ranking(&U256::from(10)),
+            vec![
+                (4, U256::MAX / 10),
+                (3, U256::from(20)),
+                (1, U256::from(15)),
+                (0, U256::from(10)),
+                (2, U256::from(10)),
+            ]
+        );
+        // Ranking doesn't set the gas prices.
+        assert!(queue.iter().all(|tx| tx.gas_price().is_zero()));
+    }
+
+    #[test]
+    fn test_slot_tx_queue_summary() {
+        let mut queue = SlotTxQueue::new();
+        assert_eq!(
+            queue.summary(),
+            "len=0 total_gas_limit=0 epochs=0 top_contracts=[]"
+        );
+        queue.enqueue(new_slot_tx(U256::from(10)));
+        queue.enqueue(new_slot_tx(U256::from(20)));
+        assert_eq!(
+            queue.summary(),
+            format!(
+                "len=2 total_gas_limit=30 epochs=1 top_contracts=[{:?}: 2]",
+                Address::from_low_u64_be(1)
+            )
+        );
+    }
+
+    #[test]
+    fn test_slot_tx_queue_gas_aggregation_does_not_overflow() {
+        let mut queue = SlotTxQueue::new();
+        for _ in 0..4 {
+            queue.enqueue(new_slot_tx(U256::MAX));
+        }
+        queue.enqueue(new_slot_tx(U256::from(1)));
+
+        assert_eq!(
+            queue.total_gas_limit(),
+            U512::from(U256::MAX) * U512::from(4) + U512::one()
+        );
+        assert_eq!(queue.pack_within_budget(&U256::MAX), 1);
+        assert_eq!(queue.pack_within_budget(&(U256::MAX - 1)), 0);
+
+        let mut small = SlotTxQueue::new();
+        small.enqueue(new_slot_tx(U256::from(10)));
+        small.enqueue(new_slot_tx(U256::MAX));
+        assert_eq!(small.pack_within_budget(&U256::from(10)), 1);
+        small.enqueue(new_slot_tx(U256::from(1)));
+        assert_eq!(small.pack_within_budget(&U256::MAX), 1);
+    }
+}
@@ -53,5 +53,6 @@ pub use crate::{
     transaction_index::TransactionIndex,
     signal::{
         SignalLocation, SlotLocation, SignalInfo, SlotInfo, Slot, SlotTx,
+        AbiArg, BindError, EmissionError, GasError, SlotTxBuilder,
     },
 };
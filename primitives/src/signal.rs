@@ -8,9 +8,69 @@
 // Slot holds essential information neccessary to create a slot transactions. 
 // These are stored in the signal slot_list.
 
-use crate::{bytes::Bytes};
-use cfx_types::{Address, U256, H256};
+use crate::{bytes::Bytes, StorageKey};
+use cfx_types::{Address, BigEndianHash, U256, U512, H256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, convert::TryFrom, error, fmt};
+
+// Serde helpers for the byte fields of the signal types. Human readable formats such as the JSON
+// used by RPC get a 0x prefixed hex string, like the other RPC types render bytes. Other formats
+// keep the plain byte sequence. RLP doesn't go through serde, so it is not affected.
+mod serde_hex_bytes {
+    use crate::bytes::Bytes;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return bytes.serialize(serializer);
+        }
+        let mut hex = String::with_capacity(2 + bytes.len() * 2);
+        hex.push_str("0x");
+        for byte in bytes {
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Bytes::deserialize(deserializer);
+        }
+        let hex = String::deserialize(deserializer)?;
+        if !hex.starts_with("0x") || hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(D::Error::custom(
+                "Invalid bytes format. Expected a 0x-prefixed hex string with even length",
+            ));
+        }
+        (2..hex.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .map_err(|e| D::Error::custom(format!("Invalid hex: {}", e)))
+            })
+            .collect()
+    }
+}
+
+// Encoding versions of the call data of a slot transaction.
+// Version 0 prepends the 4 byte method id onto the raw data emitted by the signal.
+pub const SLOT_TX_ENCODING_V0: u8 = 0;
+pub const SLOT_TX_DEFAULT_ENCODING_VERSION: u8 = SLOT_TX_ENCODING_V0;
+
+// Serde helper for the encoding version of a slot transaction. Unsupported versions are rejected
+// when deserializing, the same as when decoding RLP, so get_encoded_data never sees them.
+fn deserialize_encoding_version<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where D: serde::Deserializer<'de> {
+    let version = u8::deserialize(deserializer)?;
+    if !SlotTx::is_supported_encoding_version(version) {
+        return Err(serde::de::Error::custom(format!(
+            "Unsupported slot tx encoding version {}",
+            version
+        )));
+    }
+    Ok(version)
+}
 
 // SignalLocation and SlotLocation.
 // Structs that keeps track of the location of a signal or slot on the network.
@@ -20,6 +80,7 @@ use serde::{Deserialize, Serialize};
 )]
 pub struct SignalLocation {
     address: Address,
+    #[serde(with = "serde_hex_bytes")]
     signal_key: Bytes,
 }
 impl SignalLocation {
@@ -37,13 +98,20 @@ impl SignalLocation {
     pub fn signal_key(&self) -> &Bytes {
         &self.signal_key
     }
+    // The key bytes the SignalInfo at this location is stored under, for looking it up in raw
+    // database dumps.
+    pub fn to_storage_key_bytes(&self) -> Vec<u8> {
+        StorageKey::new_signal_key(&self.address, &self.signal_key).to_key_bytes()
+    }
 }
 
 #[derive(
-    Clone, Debug, RlpDecodable, RlpEncodable, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize,
+    Clone, Debug, RlpDecodable, RlpEncodable, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize,
+    Deserialize,
 )]
 pub struct SlotLocation {
     address: Address,
+    #[serde(with = "serde_hex_bytes")]
     slot_key: Bytes,
 }
 impl SlotLocation {
@@ -61,18 +129,40 @@ impl SlotLocation {
     pub fn slot_key(&self) -> &Bytes {
         &self.slot_key
     }
+    // The key bytes the SlotInfo at this location is stored under.
+    pub fn to_storage_key_bytes(&self) -> Vec<u8> {
+        StorageKey::new_slot_key(&self.address, &self.slot_key).to_key_bytes()
+    }
 }
 
 // SignalInfo. Holds the mapping of a signal to a list of slots that are subscribed to it. This info
 // is used when a signal is emitted. The list of slots is modified accodingly when a slot binds to it.
 #[derive(
-    Clone, Debug, RlpDecodable, RlpEncodable, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize,
+    Clone, Debug, RlpEncodable, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize,
 )]
 pub struct SignalInfo {
     location:  SignalLocation,
     slot_list: Vec::<Slot>,
 }
+// Same as the derived decoding, except that with the dedup_slot_list_on_decode feature legacy
+// duplicated slots are removed from the slot list.
+impl Decodable for SignalInfo {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let mut sig_info = SignalInfo {
+            location:  rlp.val_at(0)?,
+            slot_list: rlp.list_at(1)?,
+        };
+        if cfg!(feature = "dedup_slot_list_on_decode") {
+            sig_info.dedup_slot_list();
+        }
+        Ok(sig_info)
+    }
+}
 impl SignalInfo {
+    // The maximum number of slots that can be bound to a signal. Each bound slot becomes a slot
+    // transaction when the signal is emitted, so this bounds the work of one emission.
+    pub const DEFAULT_MAX_SLOT_LIST_LEN: usize = 1024;
+
     // Return an empty SignalInfo.
     pub fn new(owner: &Address, signal_key: &[u8]) -> Self {
         let new = SignalInfo {
@@ -81,19 +171,50 @@ impl SignalInfo {
         };
         new
     }
-    // Bind a slot to this signal.
-    pub fn add_to_slot_list(&mut self, slot_info: &SlotInfo) {
-        let slot = Slot::new(slot_info);
-        self.slot_list.push(slot);
+    // Bind a slot to this signal. Fails without changing the slot list if it already holds
+    // DEFAULT_MAX_SLOT_LIST_LEN slots.
+    pub fn add_to_slot_list(&mut self, slot_info: &SlotInfo) -> Result<(), BindError> {
+        self.add_to_slot_list_with_limit(slot_info, Self::DEFAULT_MAX_SLOT_LIST_LEN)
     }
-    // Removes a slot given a location.
-    pub fn remove_from_slot_list(&mut self, loc: &SlotLocation) {
-        for i in 0..self.slot_list.clone().len() {
-            let slot = &self.slot_list[i];
-            if slot.location().address() == loc.address() && slot.location().slot_key() == loc.slot_key() {
-                self.slot_list.remove(i);
+    // Same as add_to_slot_list, with at most max_slots slots in the slot list.
+    pub fn add_to_slot_list_with_limit(
+        &mut self, slot_info: &SlotInfo, max_slots: usize,
+    ) -> Result<(), BindError> {
+        if self.slot_list.len() >= max_slots {
+            return Err(BindError::SlotListFull { limit: max_slots });
+        }
+        self.slot_list.push(Slot::new(slot_info));
+        Ok(())
+    }
+    // Bind many slots at once. Slots already in the slot list, or repeated in slots, are skipped,
+    // and so are the slots past DEFAULT_MAX_SLOT_LIST_LEN. Returns the number of slots that were
+    // newly bound.
+    pub fn add_slots(&mut self, slots: &[SlotInfo]) -> usize {
+        let mut bound: BTreeSet<SlotLocation> =
+            self.slot_list.iter().map(|slot| slot.location().clone()).collect();
+        let mut added = 0;
+        for slot_info in slots {
+            if self.slot_list.len() >= Self::DEFAULT_MAX_SLOT_LIST_LEN {
+                break;
+            }
+            if bound.insert(slot_info.location().clone()) {
+                self.slot_list.push(Slot::new(slot_info));
+                added += 1;
             }
         }
+        added
+    }
+    // Remove slots with the same location as an earlier slot in the slot list, which state written
+    // before bindings were deduplicated may contain. Returns the number of removed slots.
+    pub fn dedup_slot_list(&mut self) -> usize {
+        let mut seen = BTreeSet::new();
+        let len = self.slot_list.len();
+        self.slot_list.retain(|slot| seen.insert(slot.location().clone()));
+        len - self.slot_list.len()
+    }
+    // Removes a slot given a location.
+    pub fn remove_from_slot_list(&mut self, loc: &SlotLocation) {
+        self.slot_list.retain(|slot| slot.location() != loc);
     }
     // Getters
     pub fn location(&self) -> &SignalLocation {
@@ -102,6 +223,44 @@ impl SignalInfo {
     pub fn slot_list(&self) -> &Vec::<Slot> {
         &self.slot_list
     }
+    // The bound slots sorted by location. The slot list keeps the binding order, which nodes that
+    // replayed the bindings in another order after a reorg don't agree on, so emission uses this
+    // order instead. Slots with the same location keep their relative order.
+    pub fn sorted_slots(&self) -> Vec<&Slot> {
+        let mut slots: Vec<&Slot> = self.slot_list.iter().collect();
+        slots.sort_by(|a, b| a.location().cmp(b.location()));
+        slots
+    }
+    // Gas limit of each bound slot, in the order of the slot list.
+    pub fn emission_gas_profile(&self) -> Vec<(SlotLocation, U256)> {
+        self.slot_list
+            .iter()
+            .map(|slot| (slot.location().clone(), *slot.gas_limit()))
+            .collect()
+    }
+    // Total gas limit of all the slot transactions one emission creates. Summed in U512, and
+    // saturates at U256::MAX.
+    pub fn total_emission_gas(&self) -> U256 {
+        U256::try_from(self.total_emission_gas_u512()).unwrap_or(U256::max_value())
+    }
+    // Check that the total gas limit of the slot transactions one emission creates fits within
+    // the block gas limit.
+    pub fn validate_emission_budget(&self, block_gas_limit: &U256) -> Result<(), EmissionError> {
+        let total = self.total_emission_gas_u512();
+        let limit = U512::from(*block_gas_limit);
+        if total > limit {
+            return Err(EmissionError::OverBudget {
+                limit: *block_gas_limit,
+                excess: U256::try_from(total - limit).unwrap_or(U256::max_value()),
+            });
+        }
+        Ok(())
+    }
+    fn total_emission_gas_u512(&self) -> U512 {
+        self.slot_list.iter().fold(U512::zero(), |total, slot| {
+            total + U512::from(*slot.gas_limit())
+        })
+    }
 }
 
 // SlotInfo. Holds the information that the owner of the slot needs maintain.
@@ -110,7 +269,7 @@ impl SignalInfo {
 // result a few things are different, most notably, we need to keep a list
 // of the signals this slot is binded to.
 #[derive(
-    Clone, Debug, RlpDecodable, RlpEncodable, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize,
+    Clone, Debug, RlpEncodable, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize,
 )]
 pub struct SlotInfo {
     // Location on the network. Used to identify this slot uniquely.
@@ -127,6 +286,29 @@ pub struct SlotInfo {
     // List of keys to the signals that this slot is binded to.
     bind_list: Vec::<SignalLocation>,
 }
+// Same as the derived decoding, except that a zero gas ratio denominator is rejected. SlotInfo::new
+// always sets it to 100, so it can only come from corrupted or crafted data, and it would make the
+// gas price of every slot transaction of the slot divide by zero.
+impl Decodable for SlotInfo {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 7 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let slot_info = SlotInfo {
+            location:              rlp.val_at(0)?,
+            method_hash:           rlp.val_at(1)?,
+            gas_sponsor:           rlp.val_at(2)?,
+            gas_limit:             rlp.val_at(3)?,
+            gas_ratio_numerator:   rlp.val_at(4)?,
+            gas_ratio_denominator: rlp.val_at(5)?,
+            bind_list:             rlp.list_at(6)?,
+        };
+        if slot_info.gas_ratio_denominator.is_zero() {
+            return Err(DecoderError::Custom("Zero slot gas ratio denominator"));
+        }
+        Ok(slot_info)
+    }
+}
 impl SlotInfo {
     // Create a new SlotInfo.
     pub fn new(
@@ -156,12 +338,7 @@ impl SlotInfo {
     }
     // Remove a signal from the bind list.
     pub fn remove_from_bind_list(&mut self, loc: &SignalLocation) {
-        for i in 0..self.bind_list.clone().len() {
-            let sig = &self.bind_list[i];
-            if sig.address() == loc.address() && sig.signal_key() == loc.signal_key() {
-                self.bind_list.remove(i);
-            }
-        }
+        self.bind_list.retain(|sig| sig != loc);
     }
     // Getters
     pub fn location(&self) -> &SlotLocation {
@@ -182,6 +359,17 @@ impl SlotInfo {
     pub fn bind_list(&self) -> &Vec<SignalLocation> {
         &self.bind_list
     }
+    // Check that slot transactions of this slot pay at least the base gas price, i.e. the gas
+    // ratio is at least 1.
+    pub fn validate_min_ratio(&self) -> Result<(), GasError> {
+        if self.gas_ratio_numerator < self.gas_ratio_denominator {
+            return Err(GasError::RatioBelowBase {
+                numerator: self.gas_ratio_numerator,
+                denominator: self.gas_ratio_denominator,
+            });
+        }
+        Ok(())
+    }
 }
 
 // Slot. Holds the information that the signal needs to maintain. Helps in the creation of
@@ -241,11 +429,69 @@ impl Slot {
     pub fn get_method_id(&self) -> Bytes {
         self.method_hash()[0..4].to_vec()
     }
+    // The gas ratio in basis points, i.e. gas_ratio_numerator * 10000 / gas_ratio_denominator, so
+    // 15000 means slot transactions pay 150% of the average gas price. Saturates at u64::MAX. A
+    // zero denominator is not a valid ratio and gives 0.
+    pub fn priority_multiplier_bps(&self) -> u64 {
+        if self.gas_ratio_denominator.is_zero() {
+            return 0;
+        }
+        let bps = U512::from(self.gas_ratio_numerator) * U512::from(10000)
+            / U512::from(self.gas_ratio_denominator);
+        if bps > U512::from(std::u64::MAX) {
+            std::u64::MAX
+        } else {
+            bps.low_u64()
+        }
+    }
+}
+
+// A typed argument of a slot handler, for building the raw data of a slot transaction
+// with encode_abi_args. Fixed size types such as uint256 and address are a single 32 byte
+// word, dynamic types such as bytes and string are variable length bytes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AbiArg {
+    Word(H256),
+    Bytes(Bytes),
+}
+impl AbiArg {
+    pub fn from_u256(value: &U256) -> Self {
+        AbiArg::Word(H256::from_uint(value))
+    }
+    // Addresses are left padded with zeros.
+    pub fn from_address(address: &Address) -> Self {
+        AbiArg::Word(H256::from(*address))
+    }
+}
+
+// Encode args in the solidity ABI layout: a head holding a 32 byte word per argument,
+// followed by a tail. Fixed size arguments are stored in the head. For each dynamic
+// argument the head holds the byte offset of its data from the start of the encoding,
+// and the tail holds its length followed by the data zero padded to a multiple of 32
+// bytes. The method id is not included, it is prepended by get_encoded_data.
+pub fn encode_abi_args(args: &[AbiArg]) -> Bytes {
+    let mut head = Vec::with_capacity(args.len() * 32);
+    let mut tail = Vec::new();
+    for arg in args {
+        match arg {
+            AbiArg::Word(word) => head.extend_from_slice(word.as_bytes()),
+            AbiArg::Bytes(data) => {
+                let offset = U256::from(args.len() * 32 + tail.len());
+                head.extend_from_slice(H256::from_uint(&offset).as_bytes());
+                tail.extend_from_slice(H256::from_uint(&U256::from(data.len())).as_bytes());
+                tail.extend_from_slice(data);
+                let padding = (32 - data.len() % 32) % 32;
+                tail.resize(tail.len() + padding, 0);
+            }
+        }
+    }
+    head.extend_from_slice(&tail);
+    head
 }
 
 // Slot transaction struct. Includes all information needed to execute 
 #[derive(
-    Clone, Debug, RlpDecodable, RlpEncodable, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize,
+    Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize,
 )]
 pub struct SlotTx {
     // Address of contract that owns this slot.
@@ -262,6 +508,7 @@ pub struct SlotTx {
     // Block number of when this transaction becomes available for execution.
     epoch_height: u64,
     // Raw byte data emitted by the signal.
+    #[serde(with = "serde_hex_bytes")]
     raw_data: Bytes,
     // Gas price. Determined during packing.
     gas_price: U256,
@@ -269,6 +516,9 @@ pub struct SlotTx {
     gas: U256,
     // Storage limit. Determined before packing.
     storage_limit: U256,
+    // Version of the scheme used to encode the call data.
+    #[serde(default, deserialize_with = "deserialize_encoding_version")]
+    encoding_version: u8,
 }
 impl SlotTx {
     pub fn new(
@@ -289,9 +539,27 @@ impl SlotTx {
             gas_price:             U256::zero(),
             gas:                   U256::zero(),
             storage_limit:         U256::zero(),
+            encoding_version:      SLOT_TX_DEFAULT_ENCODING_VERSION,
         };
         new
     }
+    // Create a slot transaction for a handler taking several typed arguments. The raw data
+    // is the ABI encoding of args, so the call data is the method id followed by it.
+    pub fn new_with_args(slot: &Slot, epoch_height: &u64, args: &[AbiArg]) -> Self {
+        Self::new(slot, epoch_height, &encode_abi_args(args))
+    }
+    // Start building a slot transaction for slot. See SlotTxBuilder for the defaults.
+    pub fn builder(slot: &Slot) -> SlotTxBuilder {
+        SlotTxBuilder {
+            slot,
+            epoch_height: 0,
+            raw_data: Bytes::new(),
+        }
+    }
+    // Check if the encoding version is supported.
+    pub fn is_supported_encoding_version(version: u8) -> bool {
+        version == SLOT_TX_ENCODING_V0
+    }
     // Getters
     pub fn location(&self) -> &SlotLocation {
         &self.location
@@ -332,24 +600,75 @@ impl SlotTx {
     pub fn storage_limit(&self) -> &U256 {
         &self.storage_limit
     }
+    pub fn encoding_version(&self) -> u8 {
+        self.encoding_version
+    }
     // Check if two slot transactions are identical.
     pub fn is_duplicated(&self, tx: &SlotTx) -> bool {
         self.location == *tx.location() && self.raw_data == tx.raw_data().clone()
         && self.epoch_height == tx.epoch_height()
     }
+    // Check if this slot transaction is what emitting a signal with raw_data at
+    // epoch_height produces for slot. The signal arguments are carried as the raw
+    // emitted data. Gas price, gas, storage limit and encoding version are set
+    // when the transaction is packed, so they are not compared.
+    pub fn matches_emission(
+        &self, slot: &Slot, epoch_height: u64, raw_data: &Bytes,
+    ) -> bool {
+        let expected = SlotTx::new(slot, &epoch_height, raw_data);
+        self.location == expected.location
+        && self.method_hash == expected.method_hash
+        && self.gas_sponsor == expected.gas_sponsor
+        && self.gas_limit == expected.gas_limit
+        && self.gas_ratio_numerator == expected.gas_ratio_numerator
+        && self.gas_ratio_denominator == expected.gas_ratio_denominator
+        && self.epoch_height == expected.epoch_height
+        && self.raw_data == expected.raw_data
+    }
 
+    // Encode the call data according to the encoding version. Only supported versions
+    // can be set, decoded or deserialized, so every version is handled here.
+    pub fn get_encoded_data(&self) -> Bytes {
+        match self.encoding_version {
+            SLOT_TX_ENCODING_V0 => self.get_encoded_data_v0(),
+            version => unreachable!("unsupported slot tx encoding version {}", version),
+        }
+    }
     // For robustness, we keep encoding and decoding to a minimum in the rust implementation.
     // All we do for data encoding is prepend the first 4 bytes of the method_hash onto the
     // raw_data. We trust that argument processing on the solidity side has already encoded 
     // the function arguments into proper ABI format.
-    pub fn get_encoded_data(&self) -> Bytes {
+    fn get_encoded_data_v0(&self) -> Bytes {
         let mut buffer = self.method_hash()[0..4].to_vec().clone();
         buffer.extend_from_slice(&self.raw_data[..]);
         buffer
     }
+    // Set the encoding version. Returns false if the version is not supported.
+    pub fn set_encoding_version(&mut self, version: u8) -> bool {
+        if !Self::is_supported_encoding_version(version) {
+            return false;
+        }
+        self.encoding_version = version;
+        true
+    }
     // Called in the transaction pool during transaction packing.
+    // The price is computed in U512 and saturates at U256::MAX, so large gas ratios
+    // can't overflow.
     pub fn calculate_and_set_gas_price(&mut self, average_gas_price: &U256) {
-        self.gas_price = average_gas_price * self.gas_ratio_numerator / self.gas_ratio_denominator;
+        self.gas_price = self.effective_gas_price(average_gas_price);
+    }
+    // The gas price calculate_and_set_gas_price would set, without setting it. Slot
+    // transactions decoded from a queue bypass the SlotInfo checks, so a zero gas ratio
+    // denominator is treated as 1 rather than crashing the packing code.
+    pub fn effective_gas_price(&self, average_gas_price: &U256) -> U256 {
+        let denominator = if self.gas_ratio_denominator.is_zero() {
+            U256::one()
+        } else {
+            self.gas_ratio_denominator
+        };
+        let gas_price = U512::from(*average_gas_price) * U512::from(self.gas_ratio_numerator)
+            / U512::from(denominator);
+        U256::try_from(gas_price).unwrap_or(U256::max_value())
     }
     // Set gas.
     pub fn set_gas(&mut self, gas: U256) {
@@ -359,6 +678,731 @@ impl SlotTx {
     pub fn set_storage_limit(&mut self, storage_limit: U256) {
         self.storage_limit = storage_limit;
     }
+    // The balance reserved from the gas sponsor before execution, i.e. gas_limit * gas_price.
+    // Computed in U512 and saturates at U256::MAX. The gas price is set when the transaction is
+    // packed, so a zero gas price means it was never set.
+    pub fn reservation(&self) -> Result<U256, GasError> {
+        if self.gas_price.is_zero() {
+            return Err(GasError::GasPriceNotSet);
+        }
+        let reservation = U512::from(self.gas_limit) * U512::from(self.gas_price);
+        Ok(U256::try_from(reservation).unwrap_or(U256::max_value()))
+    }
+    // The reservation this transaction would have once packed at average_gas_price, without
+    // setting its gas price, so that the transaction pool can skip slot transactions whose
+    // sponsor can't afford them. It is computed like calculate_and_set_gas_price followed by
+    // reservation, so the estimate matches the actual charge.
+    pub fn estimate_cost(&self, average_gas_price: &U256) -> U256 {
+        let cost = U512::from(self.gas_limit)
+            * U512::from(self.effective_gas_price(average_gas_price));
+        U256::try_from(cost).unwrap_or(U256::max_value())
+    }
+}
+
+// Builder for SlotTx, so that call sites name the fields they set and new fields can get a
+// default without breaking them. Unset fields default to epoch height 0 and empty raw data.
+// The result is identical to SlotTx::new with the same values.
+pub struct SlotTxBuilder<'a> {
+    slot: &'a Slot,
+    epoch_height: u64,
+    raw_data: Bytes,
+}
+impl<'a> SlotTxBuilder<'a> {
+    // The epoch height at which the transaction becomes available for execution.
+    pub fn epoch_height(mut self, epoch_height: u64) -> Self {
+        self.epoch_height = epoch_height;
+        self
+    }
+    // The raw data emitted by the signal, already ABI encoded.
+    pub fn raw_data(mut self, raw_data: &[u8]) -> Self {
+        self.raw_data = raw_data.to_vec();
+        self
+    }
+    // Typed arguments, ABI encoded into the raw data.
+    pub fn args(mut self, args: &[AbiArg]) -> Self {
+        self.raw_data = encode_abi_args(args);
+        self
+    }
+    pub fn build(self) -> SlotTx {
+        SlotTx::new(self.slot, &self.epoch_height, &self.raw_data)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum GasError {
+    // The gas price of a slot transaction is used before it is set.
+    GasPriceNotSet,
+    // The gas ratio of a slot is below 1, so its slot transactions would pay less than the
+    // base gas price.
+    RatioBelowBase { numerator: U256, denominator: U256 },
+}
+
+impl fmt::Display for GasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GasError::GasPriceNotSet => write!(f, "Gas price of slot transaction is not set"),
+            GasError::RatioBelowBase { numerator, denominator } => write!(
+                f, "Slot gas ratio {}/{} is below the base rate", numerator, denominator
+            ),
+        }
+    }
+}
+
+impl error::Error for GasError {
+    fn description(&self) -> &str { "Slot transaction gas error" }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum EmissionError {
+    // The total gas limit of the slots bound to a signal exceeds the block gas limit by excess,
+    // which saturates at U256::MAX.
+    OverBudget { limit: U256, excess: U256 },
+}
+
+impl fmt::Display for EmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EmissionError::OverBudget { limit, excess } => write!(
+                f, "Emission gas exceeds the block gas limit {} by {}", limit, excess
+            ),
+        }
+    }
+}
+
+impl error::Error for EmissionError {
+    fn description(&self) -> &str { "Signal emission error" }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub enum BindError {
+    // The slot list of the signal already holds limit slots.
+    SlotListFull { limit: usize },
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BindError::SlotListFull { limit } => {
+                write!(f, "Signal already has the maximum of {} bound slots", limit)
+            }
+        }
+    }
+}
+
+impl error::Error for BindError {
+    fn description(&self) -> &str { "Slot binding error" }
+}
+
+// Version 0 slot transactions are encoded without the version field so that they keep
+// the encoding used before versioning was introduced.
+impl Encodable for SlotTx {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        let item_count = if self.encoding_version == SLOT_TX_ENCODING_V0 { 11 } else { 12 };
+        stream
+            .begin_list(item_count)
+            .append(&self.location)
+            .append(&self.method_hash)
+            .append(&self.gas_sponsor)
+            .append(&self.gas_limit)
+            .append(&self.gas_ratio_numerator)
+            .append(&self.gas_ratio_denominator)
+            .append(&self.epoch_height)
+            .append(&self.raw_data)
+            .append(&self.gas_price)
+            .append(&self.gas)
+            .append(&self.storage_limit);
+        if item_count == 12 {
+            stream.append(&self.encoding_version);
+        }
+    }
+}
+
+impl Decodable for SlotTx {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        let encoding_version = match rlp.item_count()? {
+            11 => SLOT_TX_ENCODING_V0,
+            12 => rlp.val_at(11)?,
+            _ => return Err(DecoderError::RlpIncorrectListLen),
+        };
+        if !Self::is_supported_encoding_version(encoding_version) {
+            return Err(DecoderError::Custom("Unsupported slot tx encoding version"));
+        }
+        Ok(SlotTx {
+            location:              rlp.val_at(0)?,
+            method_hash:           rlp.val_at(1)?,
+            gas_sponsor:           rlp.val_at(2)?,
+            gas_limit:             rlp.val_at(3)?,
+            gas_ratio_numerator:   rlp.val_at(4)?,
+            gas_ratio_denominator: rlp.val_at(5)?,
+            epoch_height:          rlp.val_at(6)?,
+            raw_data:              rlp.val_at(7)?,
+            gas_price:             rlp.val_at(8)?,
+            gas:                   rlp.val_at(9)?,
+            storage_limit:         rlp.val_at(10)?,
+            encoding_version,
+        })
+    }
 }
 /* Signal and Slots end */
 //////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_slot() -> Slot {
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = SlotInfo::new(
+            &owner,
+            &[0x01u8, 0x02u8],
+            &H256::from_low_u64_be(7),
+            &owner,
+            &U256::from(1000),
+            &U256::from(120),
+        );
+        Slot::new(&slot_info)
+    }
+
+    fn new_slot_tx() -> SlotTx {
+        SlotTx::new(&new_slot(), &5, &vec![0x03u8, 0x04u8])
+    }
+
+    #[test]
+    fn test_location_to_storage_key_bytes() {
+        let owner = Address::from_low_u64_be(1);
+        let signal_location = SignalLocation::new(&owner, b"key");
+        let slot_location = SlotLocation::new(&owner, b"key");
+        assert_eq!(
+            signal_location.to_storage_key_bytes(),
+            StorageKey::new_signal_key(&owner, b"key").to_key_bytes()
+        );
+        assert_eq!(
+            slot_location.to_storage_key_bytes(),
+            StorageKey::new_slot_key(&owner, b"key").to_key_bytes()
+        );
+        assert_ne!(
+            signal_location.to_storage_key_bytes(),
+            slot_location.to_storage_key_bytes()
+        );
+    }
+
+    #[test]
+    fn test_signal_info_add_slots() {
+        let owner = Address::from_low_u64_be(1);
+        let new_slot_info = |slot_key: &[u8]| {
+            SlotInfo::new(
+                &owner,
+                slot_key,
+                &H256::zero(),
+                &owner,
+                &U256::from(1000),
+                &U256::from(100),
+            )
+        };
+        let mut sig_info = SignalInfo::new(&owner, b"signal");
+        sig_info.add_to_slot_list(&new_slot_info(b"a")).unwrap();
+
+        let slots = vec![
+            new_slot_info(b"a"),
+            new_slot_info(b"b"),
+            new_slot_info(b"c"),
+            new_slot_info(b"b"),
+        ];
+        assert_eq!(sig_info.add_slots(&slots), 2);
+        let keys: Vec<&[u8]> = sig_info
+            .slot_list()
+            .iter()
+            .map(|slot| slot.location().slot_key().as_ref())
+            .collect();
+        assert_eq!(keys, vec![&b"a"[..], &b"b"[..], &b"c"[..]]);
+        assert_eq!(sig_info.add_slots(&slots), 0);
+    }
+
+    #[test]
+    fn test_signal_info_sorted_slots() {
+        let new_slot_info = |owner: u64, slot_key: &[u8]| {
+            let owner = Address::from_low_u64_be(owner);
+            SlotInfo::new(
+                &owner,
+                slot_key,
+                &H256::zero(),
+                &owner,
+                &U256::from(1000),
+                &U256::from(100),
+            )
+        };
+        let slot_infos = vec![
+            new_slot_info(2, b"a"),
+            new_slot_info(1, b"b"),
+            new_slot_info(1, b"a"),
+            new_slot_info(3, b"a"),
+        ];
+        let emitter = Address::from_low_u64_be(9);
+        let mut forward = SignalInfo::new(&emitter, b"signal");
+        let mut backward = SignalInfo::new(&emitter, b"signal");
+        for slot_info in &slot_infos {
+            forward.add_to_slot_list(slot_info).unwrap();
+        }
+        for slot_info in slot_infos.iter().rev() {
+            backward.add_to_slot_list(slot_info).unwrap();
+        }
+        assert_ne!(forward.slot_list(), backward.slot_list());
+
+        let locations = |sig_info: &SignalInfo| -> Vec<SlotLocation> {
+            sig_info
+                .sorted_slots()
+                .into_iter()
+                .map(|slot| slot.location().clone())
+                .collect()
+        };
+        let mut expected: Vec<SlotLocation> = slot_infos
+            .iter()
+            .map(|slot_info| slot_info.location().clone())
+            .collect();
+        expected.sort();
+        assert_eq!(locations(&forward), expected);
+        assert_eq!(locations(&backward), expected);
+    }
+
+    #[test]
+    fn test_signal_info_slot_list_limit() {
+        let owner = Address::from_low_u64_be(1);
+        let new_slot_info = |slot_key: &[u8]| {
+            SlotInfo::new(
+                &owner,
+                slot_key,
+                &H256::zero(),
+                &owner,
+                &U256::from(1000),
+                &U256::from(100),
+            )
+        };
+        let mut sig_info = SignalInfo::new(&owner, b"signal");
+        for slot_key in &[b"a", b"b"] {
+            sig_info
+                .add_to_slot_list_with_limit(&new_slot_info(*slot_key), 2)
+                .unwrap();
+        }
+        let bound = sig_info.clone();
+        assert_eq!(
+            sig_info.add_to_slot_list_with_limit(&new_slot_info(b"c"), 2),
+            Err(BindError::SlotListFull { limit: 2 })
+        );
+        assert_eq!(sig_info, bound);
+
+        let mut sig_info = SignalInfo::new(&owner, b"signal");
+        for i in 0..SignalInfo::DEFAULT_MAX_SLOT_LIST_LEN as u32 {
+            sig_info
+                .add_to_slot_list(&new_slot_info(&i.to_be_bytes()))
+                .unwrap();
+        }
+        let bound = sig_info.clone();
+        assert_eq!(
+            sig_info.add_to_slot_list(&new_slot_info(b"full")),
+            Err(BindError::SlotListFull {
+                limit: SignalInfo::DEFAULT_MAX_SLOT_LIST_LEN,
+            })
+        );
+        assert_eq!(sig_info.add_slots(&[new_slot_info(b"full")]), 0);
+        assert_eq!(sig_info, bound);
+    }
+
+    #[test]
+    fn test_signal_info_dedup_slot_list() {
+        let owner = Address::from_low_u64_be(1);
+        let new_slot_info = |slot_key: &[u8], gas_limit: u64| {
+            SlotInfo::new(
+                &owner,
+                slot_key,
+                &H256::zero(),
+                &owner,
+                &U256::from(gas_limit),
+                &U256::from(100),
+            )
+        };
+        let mut sig_info = SignalInfo::new(&owner, b"signal");
+        assert_eq!(sig_info.dedup_slot_list(), 0);
+        sig_info.add_to_slot_list(&new_slot_info(b"a", 1000)).unwrap();
+        sig_info.add_to_slot_list(&new_slot_info(b"b", 1000)).unwrap();
+        sig_info.add_to_slot_list(&new_slot_info(b"a", 2000)).unwrap();
+        sig_info.add_to_slot_list(&new_slot_info(b"a", 1000)).unwrap();
+
+        let decoded =
+            rlp::decode::<SignalInfo>(&rlp::encode(&sig_info)).unwrap();
+        if !cfg!(feature = "dedup_slot_list_on_decode") {
+            assert_eq!(decoded, sig_info);
+        }
+
+        assert_eq!(sig_info.dedup_slot_list(), 2);
+        assert_eq!(
+            sig_info.emission_gas_profile(),
+            vec![
+                (SlotLocation::new(&owner, b"a"), U256::from(1000)),
+                (SlotLocation::new(&owner, b"b"), U256::from(1000)),
+            ]
+        );
+        if cfg!(feature = "dedup_slot_list_on_decode") {
+            assert_eq!(decoded, sig_info);
+        }
+    }
+
+    #[test]
+    fn test_signal_info_emission_gas() {
+        let owner = Address::from_low_u64_be(1);
+        let new_slot_info = |slot_key: &[u8], gas_limit: U256| {
+            SlotInfo::new(
+                &owner,
+                slot_key,
+                &H256::zero(),
+                &owner,
+                &gas_limit,
+                &U256::from(100),
+            )
+        };
+        let mut sig_info = SignalInfo::new(&owner, b"signal");
+        assert_eq!(sig_info.total_emission_gas(), U256::zero());
+
+        sig_info
+            .add_to_slot_list(&new_slot_info(b"a", U256::from(1000)))
+            .unwrap();
+        sig_info
+            .add_to_slot_list(&new_slot_info(b"b", U256::from(2000)))
+            .unwrap();
+        assert_eq!(
+            sig_info.emission_gas_profile(),
+            vec![
+                (SlotLocation::new(&owner, b"a"), U256::from(1000)),
+                (SlotLocation::new(&owner, b"b"), U256::from(2000)),
+            ]
+        );
+        assert_eq!(sig_info.total_emission_gas(), U256::from(3000));
+
+        sig_info
+            .add_to_slot_list(&new_slot_info(b"c", U256::max_value()))
+            .unwrap();
+        assert_eq!(sig_info.total_emission_gas(), U256::max_value());
+    }
+
+    #[test]
+    fn test_slot_tx_reservation() {
+        let mut slot_tx = new_slot_tx();
+        assert_eq!(slot_tx.reservation(), Err(GasError::GasPriceNotSet));
+
+        // The gas limit of the slot is 1000 and the gas ratio is 120 / 100.
+        slot_tx.calculate_and_set_gas_price(&U256::from(50));
+        assert_eq!(slot_tx.reservation(), Ok(U256::from(60_000)));
+
+        slot_tx.calculate_and_set_gas_price(&U256::max_value());
+        assert_eq!(slot_tx.reservation(), Ok(U256::max_value()));
+    }
+
+    #[test]
+    fn test_slot_tx_estimate_cost() {
+        let mut slot_tx = new_slot_tx();
+        for average_gas_price in &[U256::from(50), U256::from(3), U256::MAX] {
+            let estimate = slot_tx.estimate_cost(average_gas_price);
+            assert!(slot_tx.gas_price().is_zero());
+            let mut packed = slot_tx.clone();
+            packed.calculate_and_set_gas_price(average_gas_price);
+            assert_eq!(packed.reservation(), Ok(estimate));
+        }
+        // The gas limit of the slot is 1000 and the gas ratio is 120 / 100.
+        assert_eq!(slot_tx.estimate_cost(&U256::from(50)), 60_000.into());
+        // The gas price is rounded down before multiplying by the gas limit.
+        assert_eq!(slot_tx.estimate_cost(&U256::from(3)), 3_000.into());
+
+        slot_tx.gas_ratio_denominator = U256::zero();
+        assert_eq!(slot_tx.estimate_cost(&U256::from(50)), 6_000_000.into());
+    }
+
+    #[test]
+    fn test_signal_info_validate_emission_budget() {
+        let owner = Address::from_low_u64_be(1);
+        let mut sig_info = SignalInfo::new(&owner, b"signal");
+        assert_eq!(sig_info.validate_emission_budget(&U256::zero()), Ok(()));
+        for (slot_key, gas_limit) in &[(b"a", 1000), (b"b", 2000)] {
+            sig_info
+                .add_to_slot_list(&SlotInfo::new(
+                    &owner,
+                    *slot_key,
+                    &H256::zero(),
+                    &owner,
+                    &U256::from(*gas_limit),
+                    &U256::from(100),
+                ))
+                .unwrap();
+        }
+        assert_eq!(
+            sig_info.validate_emission_budget(&U256::from(3000)),
+            Ok(())
+        );
+        assert_eq!(
+            sig_info.validate_emission_budget(&U256::from(2500)),
+            Err(EmissionError::OverBudget {
+                limit: U256::from(2500),
+                excess: U256::from(500),
+            })
+        );
+    }
+
+    #[test]
+    fn test_slot_info_validate_min_ratio() {
+        let owner = Address::from_low_u64_be(1);
+        let new_slot_info = |gas_ratio: u64| {
+            SlotInfo::new(
+                &owner,
+                b"slot",
+                &H256::zero(),
+                &owner,
+                &U256::from(1000),
+                &U256::from(gas_ratio),
+            )
+        };
+        // The denominator of the gas ratio of a SlotInfo is 100.
+        assert_eq!(
+            new_slot_info(99).validate_min_ratio(),
+            Err(GasError::RatioBelowBase {
+                numerator: U256::from(99),
+                denominator: U256::from(100),
+            })
+        );
+        assert_eq!(new_slot_info(100).validate_min_ratio(), Ok(()));
+        assert_eq!(new_slot_info(101).validate_min_ratio(), Ok(()));
+        assert!(new_slot_info(0).validate_min_ratio().is_err());
+    }
+
+    #[test]
+    fn test_slot_priority_multiplier_bps() {
+        let owner = Address::from_low_u64_be(1);
+        let new_slot_with_ratio = |gas_ratio: U256| {
+            Slot::new(&SlotInfo::new(
+                &owner,
+                b"slot",
+                &H256::zero(),
+                &owner,
+                &U256::from(1000),
+                &gas_ratio,
+            ))
+        };
+        // The denominator of the gas ratio of a SlotInfo is 100.
+        assert_eq!(new_slot().priority_multiplier_bps(), 12000);
+        assert_eq!(
+            new_slot_with_ratio(U256::from(150)).priority_multiplier_bps(),
+            15000
+        );
+        assert_eq!(
+            new_slot_with_ratio(U256::zero()).priority_multiplier_bps(),
+            0
+        );
+        assert_eq!(
+            new_slot_with_ratio(U256::max_value()).priority_multiplier_bps(),
+            std::u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_slot_tx_matches_emission() {
+        let slot = new_slot();
+        let mut slot_tx = new_slot_tx();
+        slot_tx.set_gas(U256::from(10));
+        assert!(slot_tx.matches_emission(&slot, 5, &vec![0x03u8, 0x04u8]));
+        assert!(!slot_tx.matches_emission(&slot, 6, &vec![0x03u8, 0x04u8]));
+        assert!(!slot_tx.matches_emission(&slot, 5, &vec![0x03u8]));
+    }
+
+    #[test]
+    fn test_slot_tx_v0_encoding_is_unversioned() {
+        let slot_tx = new_slot_tx();
+        assert_eq!(slot_tx.encoding_version(), SLOT_TX_ENCODING_V0);
+
+        let encoded = rlp::encode(&slot_tx);
+        assert_eq!(Rlp::new(&encoded).item_count().unwrap(), 11);
+        let decoded = rlp::decode::<SlotTx>(&encoded).unwrap();
+        assert_eq!(decoded, slot_tx);
+        assert_eq!(decoded.get_encoded_data(), vec![0, 0, 0, 0, 0x03, 0x04]);
+    }
+
+    #[test]
+    fn test_slot_tx_unsupported_encoding_version() {
+        let mut slot_tx = new_slot_tx();
+        assert!(!slot_tx.set_encoding_version(1));
+        assert_eq!(slot_tx.encoding_version(), SLOT_TX_ENCODING_V0);
+
+        let mut stream = RlpStream::new_list(12);
+        let encoded = rlp::encode(&slot_tx);
+        for item in Rlp::new(&encoded).iter() {
+            stream.append_raw(item.as_raw(), 1);
+        }
+        stream.append(&1u8);
+        assert!(rlp::decode::<SlotTx>(&stream.out()).is_err());
+    }
+
+    fn word(value: u64) -> String { format!("{:064x}", value) }
+
+    #[test]
+    fn test_encode_abi_args() {
+        // f(uint256,bytes,address) with (0x123, "hello", 0x..01).
+        let address = Address::from_low_u64_be(1);
+        let args = vec![
+            AbiArg::from_u256(&U256::from(0x123)),
+            AbiArg::Bytes(b"hello".to_vec()),
+            AbiArg::from_address(&address),
+        ];
+        let expected = [
+            word(0x123),
+            // Offset of the bytes, right after the 3 head words.
+            word(0x60),
+            word(1),
+            word(5),
+            format!("{:0<64}", "68656c6c6f"),
+        ]
+        .concat();
+        assert_eq!(hex_string(&encode_abi_args(&args)), expected);
+
+        // g(bytes,bytes) with ("", 33 bytes of 0xff).
+        let args = vec![AbiArg::Bytes(vec![]), AbiArg::Bytes(vec![0xff; 33])];
+        let expected = [
+            word(0x40),
+            // The empty bytes only take their length word.
+            word(0x60),
+            word(0),
+            word(33),
+            "ff".repeat(32),
+            format!("{:0<64}", "ff"),
+        ]
+        .concat();
+        assert_eq!(hex_string(&encode_abi_args(&args)), expected);
+
+        assert!(encode_abi_args(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_slot_tx_new_with_args() {
+        let args = vec![
+            AbiArg::from_u256(&U256::from(7)),
+            AbiArg::Bytes(vec![0x01u8]),
+        ];
+        let slot_tx = SlotTx::new_with_args(&new_slot(), &5, &args);
+        assert_eq!(slot_tx.raw_data(), &encode_abi_args(&args));
+        // The method id is still prepended.
+        let encoded = slot_tx.get_encoded_data();
+        assert_eq!(&encoded[..4], &slot_tx.method_hash()[..4]);
+        assert_eq!(&encoded[4..], &encode_abi_args(&args)[..]);
+    }
+
+    fn hex_string(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_zero_gas_ratio_denominator() {
+        let owner = Address::from_low_u64_be(1);
+        let slot_info = SlotInfo::new(
+            &owner,
+            b"slot",
+            &H256::zero(),
+            &owner,
+            &U256::from(1000),
+            &U256::from(150),
+        );
+        assert_eq!(
+            rlp::decode::<SlotInfo>(&rlp::encode(&slot_info)),
+            Ok(slot_info.clone())
+        );
+
+        let mut stream = RlpStream::new_list(7);
+        stream
+            .append(slot_info.location())
+            .append(&H256::zero())
+            .append(&owner)
+            .append(&U256::from(1000))
+            .append(&U256::from(150))
+            .append(&U256::zero())
+            .append_list(&Vec::<SignalLocation>::new());
+        assert_eq!(
+            rlp::decode::<SlotInfo>(&stream.out()),
+            Err(DecoderError::Custom("Zero slot gas ratio denominator"))
+        );
+
+        // A slot tx with a zero denominator is priced as if it were 1.
+        let mut slot_tx = SlotTx::new(&Slot::new(&slot_info), &1, &vec![]);
+        slot_tx.gas_ratio_denominator = U256::zero();
+        slot_tx.calculate_and_set_gas_price(&U256::from(2));
+        assert_eq!(*slot_tx.gas_price(), U256::from(300));
+    }
+
+    #[test]
+    fn test_serde_hex_bytes() {
+        let slot_tx = new_slot_tx();
+        let json = serde_json::to_string(&slot_tx).unwrap();
+        assert!(json.contains(r#""slot_key":"0x0102""#));
+        assert!(json.contains(r#""raw_data":"0x0304""#));
+        assert_eq!(serde_json::from_str::<SlotTx>(&json).unwrap(), slot_tx);
+        let json = json.replace(
+            r#""encoding_version":0"#,
+            r#""encoding_version":1"#,
+        );
+        assert!(serde_json::from_str::<SlotTx>(&json).is_err());
+
+        let owner = Address::from_low_u64_be(1);
+        let mut signal_info = SignalInfo::new(&owner, &[0xabu8]);
+        signal_info
+            .add_to_slot_list(&SlotInfo::new(
+                &owner,
+                &[0x01u8],
+                &H256::zero(),
+                &owner,
+                &U256::from(1000),
+                &U256::from(100),
+            ))
+            .unwrap();
+        let json = serde_json::to_string(&signal_info).unwrap();
+        assert!(json.contains(r#""signal_key":"0xab""#));
+        assert_eq!(
+            serde_json::from_str::<SignalInfo>(&json).unwrap(),
+            signal_info
+        );
+
+        // Empty bytes and invalid hex.
+        let location = SlotLocation::new(&Address::zero(), &[]);
+        let json = serde_json::to_string(&location).unwrap();
+        assert!(json.contains(r#""slot_key":"0x""#));
+        assert_eq!(
+            serde_json::from_str::<SlotLocation>(&json).unwrap(),
+            location
+        );
+        for invalid in &["0x1", "0xzz", "0102"] {
+            let json = json.replace(
+                r#""slot_key":"0x""#,
+                &format!(r#""slot_key":"{}""#, invalid),
+            );
+            assert!(serde_json::from_str::<SlotLocation>(&json).is_err());
+        }
+
+        // RLP is unchanged.
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&Address::zero()).append(&Vec::<u8>::new());
+        assert_eq!(rlp::encode(&location), stream.out());
+    }
+
+    #[test]
+    fn test_slot_tx_builder() {
+        let slot = new_slot();
+        assert_eq!(
+            SlotTx::builder(&slot).build(),
+            SlotTx::new(&slot, &0, &vec![])
+        );
+        assert_eq!(
+            SlotTx::builder(&slot)
+                .epoch_height(5)
+                .raw_data(&[0x03u8, 0x04u8])
+                .build(),
+            new_slot_tx()
+        );
+        let args = vec![AbiArg::from_u256(&U256::from(7))];
+        assert_eq!(
+            SlotTx::builder(&slot).epoch_height(5).args(&args).build(),
+            SlotTx::new_with_args(&slot, &5, &args)
+        );
+    }
+}
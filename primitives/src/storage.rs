@@ -25,6 +25,7 @@ impl StorageLayout {
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct StorageRoot {
     pub delta: H256,
     pub intermediate: H256,
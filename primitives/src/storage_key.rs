@@ -401,7 +401,11 @@ impl<'a> StorageKey<'a> {
                 } else {
                     StorageKey::CodeRootKey(address_bytes)
                 }
-            } 
+            } else if bytes.starts_with(Self::DEPOSIT_LIST_PREFIX) {
+                StorageKey::DepositListKey(address_bytes)
+            } else if bytes.starts_with(Self::VOTE_LIST_PREFIX) {
+                StorageKey::VoteListKey(address_bytes)
+            }
             //////////////////////////////////////////////////////////////////////
             /* Signal and Slots begin */    
             else if bytes.starts_with(Self::SIGNAL_PREFIX) {
@@ -426,6 +430,9 @@ impl<'a> StorageKey<'a> {
                     StorageKey::SlotRootKey(address_bytes)
                 }
             }
+            else if bytes.starts_with(Self::SLOT_TX_QUEUE_PREFIX) {
+                StorageKey::SlotTxQueueKey(address_bytes)
+            }
             /* Signal and Slots end */
             //////////////////////////////////////////////////////////////////////
             else {
@@ -984,6 +991,29 @@ mod tests {
         let key2 = StorageKey::from_delta_mpt_key(&bytes[..]);
         assert_eq!(key, key2);
     }
+
+    #[test]
+    fn test_key_bytes_round_trip() {
+        let address = "0f572e5295c57f15886f9b263e2f6d2d6c7b5ec6"
+            .parse::<Address>()
+            .unwrap();
+        let storage_key = &[99; 32];
+
+        for key in &[
+            StorageKey::new_account_key(&address),
+            StorageKey::new_storage_root_key(&address),
+            StorageKey::new_storage_key(&address, storage_key),
+            StorageKey::new_deposit_list_key(&address),
+            StorageKey::new_vote_list_key(&address),
+            StorageKey::new_signal_key(&address, storage_key),
+            StorageKey::new_slot_key(&address, storage_key),
+            StorageKey::new_slot_tx_queue_key(&address),
+        ] {
+            let bytes = key.to_key_bytes();
+            assert_eq!(*key, StorageKey::from_key_bytes(&bytes[..]));
+        }
+    }
+
     //////////////////////////////////////////////////////////////////////
     /* Signal and Slots begin */ 
     #[test]